@@ -0,0 +1,41 @@
+use lexer::tokenize;
+use std::collections::HashMap;
+
+const SEPARATORS: &[&str] = &["|", "&", "&&", "||"];
+
+fn expand_word(word: &str, aliases: &HashMap<String, String>, seen: &mut Vec<String>) -> Option<String> {
+    let value = aliases.get(word).filter(|_| !seen.iter().any(|name| name == word))?;
+    seen.push(word.to_owned());
+    let expanded = match tokenize(value).next() {
+        Some(first) => match expand_word(first.text, aliases, seen) {
+            Some(inner) => format!("{}{}{}", &value[.. first.span.start], inner, &value[first.span.end ..]),
+            None => value.clone(),
+        },
+        None => value.clone(),
+    };
+    Some(expanded)
+}
+
+pub fn expand(line: &str, aliases: &HashMap<String, String>) -> Option<String> {
+    if aliases.is_empty() {
+        return None;
+    }
+    let mut result = String::new();
+    let mut end = 0;
+    let mut command = true;
+    for token in tokenize(line) {
+        if command {
+            if let Some(value) = expand_word(token.text, aliases, &mut Vec::new()) {
+                result.push_str(&line[end .. token.span.start]);
+                result.push_str(&value);
+                end = token.span.end;
+            }
+        }
+        command = SEPARATORS.contains(&token.text);
+    }
+    if end == 0 {
+        return None;
+    }
+    result.push_str(&line[end ..]);
+    Some(result)
+}
@@ -0,0 +1,79 @@
+use lexer::split_words;
+
+const OPENERS: &[&str] = &["if", "case", "for", "select", "while", "until"];
+const CLOSERS: &[&str] = &["fi", "esac", "done"];
+
+pub fn split(line: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut word_start = true;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if let Some(open) = quote {
+            if c == open {
+                quote = None;
+            } else {
+                escaped = c == '\\' && open == '"';
+            }
+        } else if c == '\'' || c == '"' {
+            quote = Some(c);
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '#' && word_start {
+            commands.push(&line[start .. i]);
+            start = line.len();
+            break;
+        } else if c == ';' {
+            commands.push(&line[start .. i]);
+            start = i+1;
+        }
+        word_start = c.is_whitespace() || c == ';';
+    }
+    commands.push(&line[start ..]);
+    commands.into_iter().map(str::trim).filter(|command| !command.is_empty()).collect()
+}
+
+fn is_function(words: &[&str]) -> bool {
+    match words {
+        [first, ..] if *first == "function" || first.ends_with("()") => true,
+        [_, second, ..] => *second == "()",
+        _ => false,
+    }
+}
+
+fn depth(words: &[&str]) -> isize {
+    let first = words.first().cloned().unwrap_or("");
+    let mut depth = words.iter().filter(|&&word| word == "{").count() as isize;
+    if OPENERS.contains(&first) {
+        depth += 1;
+    }
+    if CLOSERS.contains(&first) || first == "}" {
+        depth -= 1;
+    }
+    depth
+}
+
+pub fn commands(script: &str) -> Vec<Result<&str, usize>> {
+    let mut result = Vec::new();
+    let mut level = 0;
+    let mut header = false;
+    for (lineno, line) in script.lines().enumerate() {
+        for command in split(line) {
+            let words = split_words(command);
+            let start = level;
+            level = (level + depth(&words)).max(0);
+            if start == 0 && !header {
+                if level > 0 || is_function(&words) {
+                    result.push(Err(lineno+1));
+                } else {
+                    result.push(Ok(command));
+                }
+            }
+            header = start == 0 && level == 0 && is_function(&words);
+        }
+    }
+    result
+}
@@ -0,0 +1,1493 @@
+use complete::{self, Spec};
+use dirs::spell_correct;
+use frecency;
+use history::{create_private, open_private, parse_foreign, read_history, read_lines, write_history, write_lines,
+              FOREIGN_SHELLS};
+use jobs::State;
+use keymap::ACTIONS;
+use lexer::{is_name, tokenize};
+use libc::{c_int, pid_t};
+use parser::parse;
+use shell::{HOOKS, OPTIONS, Options, Rush};
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use sys::{create_temp, errno, getcwd, getpgid, kill, perror, set_errno, strftime, time};
+
+pub trait Builtin {
+    fn name(&self) -> &'static str;
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int;
+}
+
+pub const BUILTINS: &[&dyn Builtin] = &[&Alias, &Bind, &Cd, &Compgen, &Complete, &Debug, &Dirs, &Dot, &Enable, &Exit,
+                                         &Export, &Fc, &Hash, &History, &Hook, &Jump, &Jobs, &Kill, &Logout, &Popd,
+                                         &Pushd, &Pwd, &Repeat, &Rushenv, &Set, &Source, &Unalias];
+
+pub struct Io {
+    stdout: BufWriter<Box<dyn Write>>,
+    error: Option<io::Error>,
+}
+
+impl Io {
+    pub fn new(stdout: Box<dyn Write>) -> Self {
+        Self {
+            stdout: BufWriter::new(stdout),
+            error: None,
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(Box::new(io::stdout()))
+    }
+
+    pub fn write_fmt(&mut self, args: fmt::Arguments) {
+        if self.error.is_none() {
+            self.error = self.stdout.write_fmt(args).err();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.error.is_none() {
+            self.error = self.stdout.flush().err();
+        }
+    }
+
+    pub fn finish(&mut self) -> Option<io::Error> {
+        self.flush();
+        self.error.take()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Porcelain,
+}
+
+fn output_format(args: &[String]) -> Option<(Format, Vec<String>)> {
+    let mut format = Format::Text;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = match arg.as_str() {
+            "--porcelain" => {
+                format = Format::Porcelain;
+                continue;
+            },
+            "--format" => match iter.next() {
+                Some(value) => value.as_str(),
+                None => {
+                    eprintln!("{}: --format: option requires an argument", args[0]);
+                    return None;
+                },
+            },
+            arg => match arg.strip_prefix("--format=") {
+                Some(value) => value,
+                None => {
+                    rest.push(arg.to_owned());
+                    continue;
+                },
+            },
+        };
+        format = match value {
+            "text" => Format::Text,
+            "json" => Format::Json,
+            _ => {
+                eprintln!("{}: {}: unknown format", args[0], value);
+                return None;
+            },
+        };
+    }
+    Some((format, rest))
+}
+
+fn porcelain_field(s: &str) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn check_args(args: &[String], num: usize) -> bool {
+    if args.len()-1 != num {
+        eprintln!("{}: Expect {} arguments, found {}", args[0], num, args.len()-1);
+        false
+    } else {
+        true
+    }
+}
+
+pub struct Exit;
+
+impl Builtin for Exit {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], _io: &mut Io) -> c_int {
+        if !check_args(args, 0) {
+            return 1;
+        }
+        rush.exit(0);
+    }
+}
+
+pub struct Logout;
+
+impl Builtin for Logout {
+    fn name(&self) -> &'static str {
+        "logout"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], _io: &mut Io) -> c_int {
+        if !check_args(args, 0) {
+            return 1;
+        }
+        if !rush.login {
+            eprintln!("logout: not login shell: use `exit`");
+            return 1;
+        }
+        rush.exit(0);
+    }
+}
+
+pub struct Jobs;
+
+impl Builtin for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let (format, args) = match output_format(args) {
+            Some(parsed) => parsed,
+            None => return 1,
+        };
+        if !check_args(&args, 0) {
+            return 1;
+        }
+        for job in rush.jobs.iter() {
+            let (state, status) = match job.state {
+                State::Running => ("running", None),
+                State::Stopped => ("stopped", None),
+                State::Done(status) => ("done", Some(status)),
+            };
+            let pgid = job.live_pids().next().map(getpgid).filter(|&pgid| pgid != -1);
+            let pids: Vec<_> = job.pids.iter().map(|pid| pid.to_string()).collect();
+            match format {
+                Format::Text => writeln!(io, "[{}]  {:<8}  {}", job.id, job.state, job.cmd),
+                Format::Json => writeln!(io, "{{\"id\":{},\"pgid\":{},\"pids\":[{}],\"state\":\"{}\",\"status\":{},\"command\":{},\"started\":{}}}",
+                                         job.id, pgid.map_or("null".to_owned(), |pgid| pgid.to_string()), pids.join(","),
+                                         state, status.map_or("null".to_owned(), |status| status.to_string()),
+                                         json_string(&job.cmd), job.started),
+                Format::Porcelain => writeln!(io, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                                              job.id, pgid.unwrap_or(-1), pids.join(" "), state,
+                                              status.unwrap_or(-1), job.started, porcelain_field(&job.cmd)),
+            }
+        }
+        0
+    }
+}
+
+pub struct Kill;
+
+impl Builtin for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], _io: &mut Io) -> c_int {
+        if !check_args(args, 1) {
+            return 1;
+        }
+        let arg = &args[1];
+        if let Some(id) = arg.strip_prefix('%') {
+            let job = match rush.jobs.find(id) {
+                Some(job) => job,
+                None => {
+                    eprintln!("kill: {}: no such job", arg);
+                    return 1;
+                },
+            };
+            let mut status = 0;
+            for pid in job.live_pids() {
+                if kill(pid) == -1 {
+                    perror("kill");
+                    status = 1;
+                }
+            }
+            return status;
+        }
+        match arg.parse::<pid_t>() {
+            Ok(pid) => {
+                if kill(pid) == -1 {
+                    perror("kill");
+                    return 1;
+                }
+                0
+            },
+            Err(_) => {
+                eprintln!("kill: {} isn't an integer", arg);
+                1
+            },
+        }
+    }
+}
+
+pub struct Fc;
+
+impl Builtin for Fc {
+    fn name(&self) -> &'static str {
+        "fc"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let mut list = false;
+        let mut numbers = true;
+        let mut reverse = false;
+        let mut subst = false;
+        let mut editor = None;
+        let mut args = args[1 ..].iter();
+        let mut rest = Vec::new();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg.len() == 1 || arg[1 ..].parse::<usize>().is_ok() {
+                rest.push(arg.as_str());
+                continue;
+            }
+            for letter in arg[1 ..].chars() {
+                match letter {
+                    'l' => list = true,
+                    'n' => numbers = false,
+                    'r' => reverse = true,
+                    's' => subst = true,
+                    'e' => match args.next() {
+                        Some(arg) => editor = Some(arg.clone()),
+                        None => {
+                            eprintln!("fc: -e: option requires an argument");
+                            return 1;
+                        },
+                    },
+                    _ => {
+                        eprintln!("fc: -{}: invalid option", letter);
+                        return 1;
+                    },
+                }
+            }
+        }
+        if subst {
+            let (pat, rep) = match rest.first().cloned().and_then(|arg| arg.find('=').map(|pos| (arg, pos))) {
+                Some((arg, pos)) => {
+                    rest.remove(0);
+                    (&arg[.. pos], &arg[pos+1 ..])
+                },
+                None => ("", ""),
+            };
+            let hisno = match rush.history.resolve(rest.first().cloned().unwrap_or("-1")) {
+                Some(hisno) => hisno,
+                None => {
+                    eprintln!("fc: no command found");
+                    return 1;
+                },
+            };
+            let mut line = rush.history.get(hisno).unwrap().clone();
+            if !pat.is_empty() {
+                line = line.replace(pat, rep);
+            }
+            writeln!(io, "{}", line);
+            io.flush();
+            rush.history_replaced = true;
+            rush.add_history(line.clone());
+            rush.execute(&line);
+            return rush.status;
+        }
+        let default = if list { "-16" } else { "-1" };
+        let first = rest.first().cloned().unwrap_or(default);
+        let last = rest.get(1).cloned().unwrap_or(if list { "-1" } else { first });
+        let (first, last) = match (rush.history.resolve(first), rush.history.resolve(last)) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                eprintln!("fc: history specification out of range");
+                return 1;
+            },
+        };
+        let (first, last, reverse) = if first > last {
+            (last, first, !reverse)
+        } else {
+            (first, last, reverse)
+        };
+        let mut entries: Vec<_> = rush.history.iter()
+            .filter(|&(hisno, _)| first <= hisno && hisno <= last)
+            .map(|(hisno, entry)| (hisno, entry.line.clone()))
+            .collect();
+        if reverse {
+            entries.reverse();
+        }
+        if list {
+            for (hisno, line) in entries {
+                if numbers {
+                    writeln!(io, "{}\t {}", hisno, line);
+                } else {
+                    writeln!(io, "\t {}", line);
+                }
+            }
+            return 0;
+        }
+        let editor = editor
+            .or_else(|| rush.vars.get("FCEDIT").map(|s| s.to_owned()))
+            .or_else(|| rush.vars.get("EDITOR").map(|s| s.to_owned()))
+            .unwrap_or_else(|| "vi".to_owned());
+        let tmpdir = Path::new(rush.vars.get("TMPDIR").unwrap_or("/tmp"));
+        let path = match create_temp(tmpdir, "rush-fc.", "") {
+            Ok((path, file)) => match write_lines(&file, entries.iter().map(|entry| &entry.1)) {
+                Ok(()) => path,
+                Err(error) => {
+                    let _ = fs::remove_file(&path);
+                    eprintln!("fc: {}: {}", path.display(), error);
+                    return 1;
+                },
+            },
+            Err(error) => {
+                eprintln!("fc: {}: {}", tmpdir.display(), error);
+                return 1;
+            },
+        };
+        rush.execute(&format!("{} {}", editor, quote(&path.to_string_lossy())));
+        let lines = read_lines(&path);
+        let _ = fs::remove_file(&path);
+        if rush.status != 0 {
+            return rush.status;
+        }
+        let lines = match lines {
+            Ok(lines) => lines,
+            Err(error) => {
+                eprintln!("fc: {}: {}", path.display(), error);
+                return 1;
+            },
+        };
+        rush.history_replaced = true;
+        for line in lines {
+            writeln!(io, "{}", line);
+            io.flush();
+            rush.add_history(line.clone());
+            rush.execute(&line);
+        }
+        rush.status
+    }
+}
+
+pub struct Cd;
+
+impl Builtin for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let mut physical = false;
+        let mut args = &args[1 ..];
+        while let Some(arg) = args.first() {
+            if arg.len() < 2 || !arg.starts_with('-') || !arg[1 ..].chars().all(|c| c == 'L' || c == 'P') {
+                break;
+            }
+            physical = arg.ends_with('P');
+            args = &args[1 ..];
+        }
+        let old = rush.cwd.clone();
+        if args.len() == 2 {
+            if !old.contains(args[0].as_str()) {
+                eprintln!("cd: string not in pwd: {}", args[0]);
+                return 1;
+            }
+            let dir = old.replacen(args[0].as_str(), &args[1], 1);
+            if rush.chdir(&dir, physical) == -1 {
+                perror(&("cd: ".to_owned() + &dir));
+                return 1;
+            }
+            writeln!(io, "{}", rush.tilde(rush.cwd.clone()));
+            rush.auto_pushd(old);
+            return 0;
+        }
+        if args.len() != 1 {
+            eprintln!("cd: Expect 1 or 2 arguments, found {}", args.len());
+            return 1;
+        }
+        let dir = &args[0];
+        if (dir.starts_with('+') || dir.starts_with('-')) && dir[1 ..].parse::<usize>().is_ok() {
+            let index = match rush.stack_index(dir) {
+                Some(index) => index,
+                None => {
+                    eprintln!("cd: {}: directory stack index out of range", dir);
+                    return 1;
+                },
+            };
+            if index == 0 {
+                return 0;
+            }
+            let target = rush.dirs[index-1].clone();
+            if rush.chdir(&target, physical) == -1 {
+                perror(&("cd: ".to_owned() + &target));
+                return 1;
+            }
+            rush.dirs.remove(index-1);
+            rush.auto_pushd(old);
+            return 0;
+        }
+        let relative = !dir.starts_with('/') && dir != "." && dir != ".." &&
+            !dir.starts_with("./") && !dir.starts_with("../");
+        if relative && !Path::new(dir).is_dir() {
+            let cdpath = rush.vars.get("CDPATH").unwrap_or("").to_string();
+            for base in cdpath.split(':').filter(|base| !base.is_empty()) {
+                let path = Path::new(base).join(dir);
+                if path.is_dir() && rush.chdir(&path.to_string_lossy(), physical) == 0 {
+                    writeln!(io, "{}", rush.cwd);
+                    rush.auto_pushd(old);
+                    return 0;
+                }
+            }
+        }
+        if rush.chdir(dir, physical) == -1 {
+            let error = errno();
+            let corrected = if rush.options.cdspell { spell_correct(dir) } else { None };
+            match corrected {
+                Some(corrected) if rush.chdir(&corrected, physical) == 0 => writeln!(io, "{}", corrected),
+                _ => {
+                    set_errno(error);
+                    perror(&("cd: ".to_owned() + dir));
+                    return 1;
+                },
+            }
+        }
+        rush.auto_pushd(old);
+        0
+    }
+}
+
+pub struct Jump;
+
+impl Builtin for Jump {
+    fn name(&self) -> &'static str {
+        "j"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let file = match rush.dirfile() {
+            Some(file) => file,
+            None => {
+                eprintln!("j: HOME is not set");
+                return 1;
+            },
+        };
+        let mut list = false;
+        let mut words = Vec::new();
+        for arg in &args[1 ..] {
+            match arg.as_str() {
+                "-l" => list = true,
+                "-x" => {
+                    if let Err(error) = frecency::forget(&file, &rush.cwd) {
+                        eprintln!("j: {}: {}", file, error);
+                        return 1;
+                    }
+                    return 0;
+                },
+                _ => words.push(arg.clone()),
+            }
+        }
+        let now = time();
+        let entries = frecency::search(&file, &words, now);
+        if list || words.is_empty() {
+            for entry in entries.iter().rev() {
+                writeln!(io, "{:<10.1} {}", entry.score(now), rush.tilde(entry.path.clone()));
+            }
+            return 0;
+        }
+        let target = entries.iter().find(|entry| entry.path != rush.cwd && Path::new(&entry.path).is_dir());
+        match target {
+            Some(entry) => {
+                let dir = entry.path.clone();
+                if rush.chdir(&dir, false) == -1 {
+                    perror(&("j: ".to_owned() + &dir));
+                    return 1;
+                }
+                0
+            },
+            None => {
+                eprintln!("j: {}: no matching directory", words.join(" "));
+                1
+            },
+        }
+    }
+}
+
+pub struct Pwd;
+
+impl Builtin for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let mut physical = false;
+        for arg in &args[1 ..] {
+            match arg.as_str() {
+                "-L" => physical = false,
+                "-P" => physical = true,
+                _ => {
+                    eprintln!("pwd: {}: invalid argument", arg);
+                    return 1;
+                },
+            }
+        }
+        if physical {
+            writeln!(io, "{}", getcwd());
+        } else {
+            writeln!(io, "{}", rush.cwd);
+        }
+        0
+    }
+}
+
+pub struct Dirs;
+
+impl Builtin for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let mut clear = false;
+        let mut verbose = false;
+        let mut long = false;
+        let mut index = None;
+        for arg in &args[1 ..] {
+            if arg.starts_with('+') || (arg.starts_with('-') && arg[1 ..].parse::<usize>().is_ok()) {
+                index = match rush.stack_index(arg) {
+                    Some(index) => Some(index),
+                    None => {
+                        eprintln!("dirs: {}: directory stack index out of range", arg);
+                        return 1;
+                    },
+                };
+                continue;
+            }
+            if !arg.starts_with('-') || arg.len() == 1 {
+                eprintln!("dirs: {}: invalid argument", arg);
+                return 1;
+            }
+            for letter in arg[1 ..].chars() {
+                match letter {
+                    'c' => clear = true,
+                    'l' => long = true,
+                    'v' => verbose = true,
+                    _ => {
+                        eprintln!("dirs: -{}: invalid option", letter);
+                        return 1;
+                    },
+                }
+            }
+        }
+        if clear {
+            rush.dirs.clear();
+            return 0;
+        }
+        let stack = rush.dir_stack();
+        let show = |dir: &String| if long { dir.clone() } else { rush.tilde(dir.clone()) };
+        if let Some(index) = index {
+            writeln!(io, "{}", show(&stack[index]));
+        } else if verbose {
+            for (i, dir) in stack.iter().enumerate() {
+                writeln!(io, "{:2}  {}", i, show(dir));
+            }
+        } else {
+            writeln!(io, "{}", stack.iter().map(show).collect::<Vec<_>>().join(" "));
+        }
+        0
+    }
+}
+
+pub struct Pushd;
+
+impl Builtin for Pushd {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        if args.len() > 2 {
+            eprintln!("pushd: too many arguments");
+            return 1;
+        }
+        let mut stack = rush.dir_stack();
+        match args.get(1) {
+            None => {
+                if stack.len() < 2 {
+                    eprintln!("pushd: no other directory");
+                    return 1;
+                }
+                stack.swap(0, 1);
+            },
+            Some(arg) if arg.starts_with('+') || (arg.starts_with('-') && arg.len() > 1) => {
+                match rush.stack_index(arg) {
+                    Some(index) => stack.rotate_left(index),
+                    None => {
+                        eprintln!("pushd: {}: directory stack index out of range", arg);
+                        return 1;
+                    },
+                }
+            },
+            Some(dir) => {
+                if rush.chdir(dir, false) == -1 {
+                    perror(&("pushd: ".to_owned() + dir));
+                    return 1;
+                }
+                rush.dirs.insert(0, stack.remove(0));
+                return print_dirs(rush, io);
+            },
+        }
+        if rush.chdir(&stack[0], false) == -1 {
+            perror(&("pushd: ".to_owned() + &stack[0]));
+            return 1;
+        }
+        stack.remove(0);
+        rush.dirs = stack;
+        print_dirs(rush, io)
+    }
+}
+
+pub struct Popd;
+
+impl Builtin for Popd {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        if args.len() > 2 {
+            eprintln!("popd: too many arguments");
+            return 1;
+        }
+        if rush.dirs.is_empty() {
+            eprintln!("popd: directory stack empty");
+            return 1;
+        }
+        let index = match args.get(1) {
+            Some(arg) => match rush.stack_index(arg) {
+                Some(index) => index,
+                None => {
+                    eprintln!("popd: {}: directory stack index out of range", arg);
+                    return 1;
+                },
+            },
+            None => 0,
+        };
+        if index == 0 {
+            let dir = rush.dirs[0].clone();
+            if rush.chdir(&dir, false) == -1 {
+                perror(&("popd: ".to_owned() + &dir));
+                return 1;
+            }
+        }
+        rush.dirs.remove(index.max(1) - 1);
+        print_dirs(rush, io)
+    }
+}
+
+fn print_dirs(rush: &Rush, io: &mut Io) -> c_int {
+    let stack: Vec<_> = rush.dir_stack().into_iter().map(|dir| rush.tilde(dir)).collect();
+    writeln!(io, "{}", stack.join(" "));
+    0
+}
+
+pub struct Complete;
+
+impl Builtin for Complete {
+    fn name(&self) -> &'static str {
+        "complete"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let args = &args[1 ..];
+        match args.first().map(String::as_str) {
+            None | Some("-p") => {
+                let names = if args.len() > 1 { &args[1 ..] } else { &[] };
+                let mut specs: Vec<_> = rush.completions.iter()
+                    .filter(|&(name, _)| names.is_empty() || names.contains(name))
+                    .collect();
+                specs.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, spec) in specs {
+                    writeln!(io, "complete {} {}", spec.to_args(), name);
+                }
+                return 0;
+            },
+            Some("-r") => {
+                if args.len() == 1 {
+                    rush.completions.clear();
+                }
+                for name in &args[1 ..] {
+                    rush.completions.remove(name);
+                }
+                return 0;
+            },
+            Some("--load") => {
+                let lib = rush.vars.get("BASH_COMPLETION").unwrap_or(complete::BASH_COMPLETION).to_owned();
+                let mut status = 0;
+                for path in &args[1 ..] {
+                    match complete::load_bash(&lib, path) {
+                        Ok((specs, skipped)) => {
+                            for (spec, names) in specs {
+                                for name in names {
+                                    rush.completions.insert(name, spec.clone());
+                                }
+                            }
+                            for error in skipped {
+                                eprintln!("complete: {}: skipped {}", path, error);
+                            }
+                        },
+                        Err(error) => {
+                            eprintln!("complete: {}", error);
+                            status = 1;
+                        },
+                    }
+                }
+                return status;
+            },
+            _ => {},
+        }
+        let (spec, i) = match Spec::parse(args) {
+            Ok((Spec { function: Some(ref function), .. }, _)) => {
+                eprintln!("complete: -F: {}: shell functions are only supported through complete --load", function);
+                return 1;
+            },
+            Ok(spec) => spec,
+            Err(error) => {
+                eprintln!("complete: {}", error);
+                return 1;
+            },
+        };
+        if i == args.len() {
+            eprintln!("complete: no command name given");
+            return 1;
+        }
+        for name in &args[i ..] {
+            rush.completions.insert(name.clone(), spec.clone());
+        }
+        0
+    }
+}
+
+pub struct Compgen;
+
+impl Builtin for Compgen {
+    fn name(&self) -> &'static str {
+        "compgen"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let args = &args[1 ..];
+        let (spec, i) = match Spec::parse(args) {
+            Ok(spec) => spec,
+            Err(error) => {
+                eprintln!("compgen: {}", error);
+                return 1;
+            },
+        };
+        if args.len() > i + 1 {
+            eprintln!("compgen: too many arguments");
+            return 1;
+        }
+        let word = args.get(i).map_or("", String::as_str);
+        let candidates = rush.compgen(&spec, word, &[word.to_string()], 0);
+        for candidate in &candidates {
+            writeln!(io, "{}", complete::unescape(candidate));
+        }
+        if candidates.is_empty() { 1 } else { 0 }
+    }
+}
+
+pub struct Hash;
+
+impl Builtin for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let mut named = false;
+        let mut clear = false;
+        let mut args = args[1 ..].iter().peekable();
+        while let Some(arg) = args.peek() {
+            if !arg.starts_with('-') || arg.len() == 1 {
+                break;
+            }
+            for letter in arg[1 ..].chars() {
+                match letter {
+                    'd' => named = true,
+                    'r' => clear = true,
+                    _ => {
+                        eprintln!("hash: -{}: invalid option", letter);
+                        return 1;
+                    },
+                }
+            }
+            args.next();
+        }
+        if !named {
+            eprintln!("hash: only named directories (-d) are supported");
+            return 1;
+        }
+        if clear {
+            rush.named_dirs.clear();
+        } else if args.peek().is_none() {
+            let mut names: Vec<_> = rush.named_dirs.iter().collect();
+            names.sort();
+            for (name, dir) in names {
+                writeln!(io, "{}={}", name, dir);
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for arg in args {
+            match arg.find('=') {
+                Some(pos) if is_name(&arg[.. pos]) && pos+1 < arg.len() => {
+                    let dir = arg[pos+1 ..].trim_end_matches('/');
+                    let dir = if dir.is_empty() { "/" } else { dir };
+                    rush.named_dirs.insert(arg[.. pos].to_string(), dir.to_string());
+                },
+                _ => match rush.named_dirs.get(arg.as_str()) {
+                    Some(dir) => writeln!(io, "{}={}", arg, dir),
+                    None => {
+                        eprintln!("hash: {}: not a named directory", arg);
+                        status = 1;
+                    },
+                },
+            }
+        }
+        status
+    }
+}
+
+pub struct History;
+
+impl Builtin for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let (format, args) = match output_format(args) {
+            Some(parsed) => parsed,
+            None => return 1,
+        };
+        let args = &args[1 ..];
+        let opt = match args.first() {
+            Some(arg) if arg.starts_with('-') && arg.len() > 1 => arg.as_str(),
+            _ => "",
+        };
+        match opt {
+            "" => {
+                if args.len() > 1 {
+                    eprintln!("history: too many arguments");
+                    return 1;
+                }
+                let count = match args.first() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(count) => count,
+                        Err(_) => {
+                            eprintln!("history: {}: numeric argument required", arg);
+                            return 1;
+                        },
+                    },
+                    None => rush.history.entries.len(),
+                };
+                let skip = rush.history.entries.len().saturating_sub(count);
+                let timeformat = rush.vars.get("HISTTIMEFORMAT");
+                for (hisno, entry) in rush.history.iter().skip(skip) {
+                    match timeformat {
+                        _ if format == Format::Json => {
+                            let time = if entry.time == 0 { "null".to_owned() } else { entry.time.to_string() };
+                            writeln!(io, "{{\"number\":{},\"time\":{},\"command\":{}}}", hisno, time, json_string(&entry.line));
+                        },
+                        _ if format == Format::Porcelain => {
+                            writeln!(io, "{}\t{}\t{}", hisno, entry.time, porcelain_field(&entry.line));
+                        },
+                        Some(timeformat) if entry.time != 0 => {
+                            writeln!(io, "{:>5}  {}{}", hisno, strftime(timeformat, entry.time), entry.line);
+                        },
+                        _ => writeln!(io, "{:>5}  {}", hisno, entry.line),
+                    }
+                }
+            },
+            "-c" => {
+                rush.history.clear();
+            },
+            "--import" => {
+                if args.len() < 2 || args.len() > 3 {
+                    eprintln!("history: usage: history --import bash|zsh|fish [file]");
+                    return 1;
+                }
+                if !FOREIGN_SHELLS.contains(&args[1].as_str()) {
+                    eprintln!("history: {}: unsupported shell", args[1]);
+                    return 1;
+                }
+                let path = match args.get(2).cloned().or_else(|| rush.foreign_histfile(&args[1])) {
+                    Some(path) => path,
+                    None => {
+                        eprintln!("history: HOME is not set");
+                        return 1;
+                    },
+                };
+                let result = fs::read(&path).and_then(|content| {
+                    let entries = parse_foreign(&args[1], &content).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("not a valid {} history file", args[1]))
+                    })?;
+                    rush.import_history(&entries).map(|_| entries.len())
+                });
+                match result {
+                    Ok(count) => writeln!(io, "history: imported {} entries from {}", count, path),
+                    Err(error) => {
+                        eprintln!("history: {}: {}", path, error);
+                        return 1;
+                    },
+                }
+            },
+            "-d" => {
+                if !check_args(args, 2) {
+                    return 1;
+                }
+                let last = (rush.history.base + rush.history.entries.len()) as isize;
+                let hisno = match args[1].parse::<isize>() {
+                    Ok(n) if n < 0 => last + 1 + n,
+                    Ok(n) => n,
+                    Err(_) => 0,
+                };
+                if hisno <= 0 || rush.history.remove(hisno as usize).is_none() {
+                    eprintln!("history: {}: history position out of range", args[1]);
+                    return 1;
+                }
+            },
+            "-a" | "-r" | "-w" => {
+                if args.len() > 2 {
+                    eprintln!("history: too many arguments");
+                    return 1;
+                }
+                let path = match args.get(1).cloned().or_else(|| rush.histfile()) {
+                    Some(path) => path,
+                    None => {
+                        eprintln!("history: HISTFILE is not set");
+                        return 1;
+                    },
+                };
+                let timestamps = rush.vars.get("HISTTIMEFORMAT").is_some();
+                let histfile = rush.histfile().as_ref() == Some(&path);
+                let result = match opt {
+                    "-a" if histfile => rush.save_history(),
+                    "-a" => open_private(&path, OpenOptions::new().create(true).append(true))
+                        .and_then(|mut file| rush.history.append(&mut file, timestamps)),
+                    "-w" => create_private(&path).and_then(|file| {
+                        write_history(&file, &rush.history.entries, timestamps)?;
+                        if histfile {
+                            rush.history.offset = file.metadata()?.len();
+                            rush.history.index.clear();
+                        }
+                        Ok(())
+                    }),
+                    _ => read_history(&path).map(|(entries, _)| {
+                        let size = rush.histsize("HISTSIZE").unwrap_or(1000);
+                        rush.history.extend(entries, size);
+                    }),
+                };
+                if let Err(error) = result {
+                    eprintln!("history: {}: {}", path, error);
+                    return 1;
+                }
+            },
+            _ => {
+                eprintln!("history: {}: invalid option", opt);
+                return 1;
+            },
+        }
+        0
+    }
+}
+
+pub struct Set;
+
+impl Builtin for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let args = &args[1 ..];
+        if args.is_empty() {
+            let mut vars: Vec<_> = rush.vars.vars.iter().collect();
+            vars.sort();
+            for (name, value) in vars {
+                writeln!(io, "{}={}", name, value);
+            }
+            return 0;
+        }
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            let on = arg.starts_with('-');
+            if !on && !arg.starts_with('+') || arg.len() < 2 {
+                eprintln!("set: {}: invalid option", arg);
+                return 1;
+            }
+            if &arg[1 ..] == "o" {
+                i += 1;
+                if i == args.len() {
+                    for opt in OPTIONS {
+                        let value = rush.options.get(opt.0).unwrap();
+                        writeln!(io, "{:<15} {}", opt.0, if value { "on" } else { "off" });
+                    }
+                    return 0;
+                }
+                if !rush.options.set(&args[i], on) {
+                    eprintln!("set: {}: invalid option name", args[i]);
+                    return 1;
+                }
+            } else {
+                for letter in arg[1 ..].chars() {
+                    match Options::by_letter(letter) {
+                        Some(name) => {
+                            rush.options.set(name, on);
+                        },
+                        None => {
+                            eprintln!("set: {}{}: invalid option", &arg[.. 1], letter);
+                            return 1;
+                        },
+                    }
+                }
+            }
+            i += 1;
+        }
+        0
+    }
+}
+
+pub struct Debug;
+
+impl Builtin for Debug {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    fn run(&self, _rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let line = args.get(2 ..).unwrap_or(&[]).join(" ");
+        match args.get(1).map(String::as_str) {
+            Some("tokens") => {
+                for token in tokenize(&line) {
+                    writeln!(io, "{:?}\t{}", token.span, token.text);
+                }
+                0
+            },
+            Some("ast") => match parse(&line) {
+                Ok(cmdlines) => {
+                    writeln!(io, "{:#?}", cmdlines);
+                    0
+                },
+                Err(error) => {
+                    eprintln!("{}", error);
+                    error.status()
+                },
+            },
+            _ => {
+                eprintln!("debug: Expect tokens or ast");
+                2
+            },
+        }
+    }
+}
+
+pub struct Hook;
+
+impl Builtin for Hook {
+    fn name(&self) -> &'static str {
+        "hook"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let (remove, args) = match args.get(1).map(String::as_str) {
+            Some("-d") => (true, &args[2 ..]),
+            _ => (false, &args[1 ..]),
+        };
+        let names = match args.first() {
+            Some(name) => match HOOKS.iter().find(|hook| *hook == name) {
+                Some(name) => vec![*name],
+                None => {
+                    eprintln!("hook: {}: unknown hook", name);
+                    return 1;
+                },
+            },
+            None if remove => {
+                eprintln!("hook: -d: Expect a hook name");
+                return 2;
+            },
+            None => HOOKS.to_vec(),
+        };
+        let cmd = args.get(1 ..).unwrap_or(&[]).join(" ");
+        if remove {
+            let cmds = rush.hooks.entry(names[0]).or_default();
+            cmds.retain(|hook| !cmd.is_empty() && *hook != cmd);
+        } else if cmd.is_empty() {
+            for name in names {
+                for cmd in rush.hooks.get(name).into_iter().flatten() {
+                    writeln!(io, "hook {} {}", name, cmd);
+                }
+            }
+        } else {
+            rush.hooks.entry(names[0]).or_default().push(cmd);
+        }
+        0
+    }
+}
+
+pub struct Rushenv;
+
+impl Builtin for Rushenv {
+    fn name(&self) -> &'static str {
+        "rushenv"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        match args.get(1).map(String::as_str) {
+            Some(cmd @ ("allow" | "deny")) if args.len() <= 3 => {
+                let dir = args.get(2).cloned().unwrap_or_else(|| {
+                    rush.dir_env.as_ref().map_or_else(|| rush.cwd.clone(), |dir_env| dir_env.dir.clone())
+                });
+                if let Err(error) = rush.allow_env(&dir, cmd == "allow") {
+                    eprintln!("rushenv: {}: {}", dir, error);
+                    return 1;
+                }
+            },
+            Some("list") if args.len() == 2 => {
+                for dir in rush.allowed_envs() {
+                    writeln!(io, "{}", dir);
+                }
+            },
+            Some("reload") if args.len() == 2 => rush.update_dir_env(true),
+            _ => {
+                eprintln!("rushenv: usage: rushenv allow|deny [dir] | list | reload");
+                return 2;
+            },
+        }
+        0
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub struct Alias;
+
+impl Builtin for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        if args.len() == 1 {
+            let mut aliases: Vec<_> = rush.aliases.iter().collect();
+            aliases.sort();
+            for (name, value) in aliases {
+                writeln!(io, "alias {}={}", name, quote(value));
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for arg in &args[1 ..] {
+            match arg.find('=') {
+                Some(pos) if pos > 0 && !arg[.. pos].contains(['/', '$', '\'', '"', '\\', '|', '&', '<', '>']) => {
+                    rush.aliases.insert(arg[.. pos].to_string(), arg[pos+1 ..].to_string());
+                },
+                Some(_) => {
+                    eprintln!("alias: {}: invalid alias name", arg);
+                    status = 1;
+                },
+                None => match rush.aliases.get(arg) {
+                    Some(value) => writeln!(io, "alias {}={}", arg, quote(value)),
+                    None => {
+                        eprintln!("alias: {}: not found", arg);
+                        status = 1;
+                    },
+                },
+            }
+        }
+        status
+    }
+}
+
+pub struct Unalias;
+
+impl Builtin for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], _io: &mut Io) -> c_int {
+        if args.get(1).is_some_and(|arg| arg == "-a") {
+            rush.aliases.clear();
+            return 0;
+        }
+        let mut status = 0;
+        for arg in &args[1 ..] {
+            if rush.aliases.remove(arg).is_none() {
+                eprintln!("unalias: {}: not found", arg);
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+pub struct Export;
+
+impl Builtin for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let args: Vec<_> = args[1 ..].iter().filter(|arg| *arg != "-p").collect();
+        if args.is_empty() {
+            let mut vars: Vec<_> = env::vars().collect();
+            vars.sort();
+            for (name, value) in vars {
+                writeln!(io, "export {}={}", name, quote(&value));
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.find('=') {
+                Some(pos) => (&arg[.. pos], Some(&arg[pos+1 ..])),
+                None => (arg.as_str(), None),
+            };
+            if !is_name(name) {
+                eprintln!("export: {}: not a valid identifier", arg);
+                status = 1;
+                continue;
+            }
+            let value = match value.or_else(|| rush.vars.get(name)) {
+                Some(value) => value.to_owned(),
+                None => continue,
+            };
+            env::set_var(name, &value);
+            rush.vars.set(name, &value);
+        }
+        status
+    }
+}
+
+pub struct Source;
+
+impl Builtin for Source {
+    fn name(&self) -> &'static str {
+        "source"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        if !check_args(args, 1) {
+            return 2;
+        }
+        io.flush();
+        match rush.source_file(&args[0], &args[1]) {
+            Ok(status) => status,
+            Err(error) => {
+                eprintln!("{}: {}: {}", args[0], args[1], error);
+                1
+            },
+        }
+    }
+}
+
+pub struct Dot;
+
+impl Builtin for Dot {
+    fn name(&self) -> &'static str {
+        "."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        Source.run(rush, args, io)
+    }
+}
+
+pub struct Bind;
+
+impl Builtin for Bind {
+    fn name(&self) -> &'static str {
+        "bind"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        if args.len() == 1 {
+            for line in rush.keymap.lines() {
+                writeln!(io, "{}", line);
+            }
+            return 0;
+        }
+        let mut status = 0;
+        let mut args = args[1 ..].iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-l" => {
+                    for &(name, _) in ACTIONS {
+                        writeln!(io, "{}", name);
+                    }
+                },
+                "-p" => {
+                    for line in rush.keymap.lines() {
+                        writeln!(io, "{}", line);
+                    }
+                },
+                "-f" | "-r" => {
+                    let value = match args.next() {
+                        Some(value) => value,
+                        None => {
+                            eprintln!("bind: {}: option requires an argument", arg);
+                            return 1;
+                        },
+                    };
+                    if arg == "-r" {
+                        if let Err(error) = rush.keymap.unbind(value) {
+                            eprintln!("bind: {}", error);
+                            status = 1;
+                        }
+                        continue;
+                    }
+                    match rush.read_inputrc(value, 0) {
+                        Ok(errors) => {
+                            for (path, lineno, error) in errors {
+                                eprintln!("bind: {}: line {}: {}", path, lineno, error);
+                                status = 1;
+                            }
+                        },
+                        Err(error) => {
+                            eprintln!("bind: {}: {}", value, error);
+                            status = 1;
+                        },
+                    }
+                },
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    eprintln!("bind: {}: invalid option", arg);
+                    return 1;
+                },
+                _ => {
+                    if let Err(error) = rush.keymap.bind(arg) {
+                        eprintln!("bind: {}", error);
+                        status = 1;
+                    }
+                },
+            }
+        }
+        status
+    }
+}
+
+pub struct Repeat;
+
+impl Builtin for Repeat {
+    fn name(&self) -> &'static str {
+        "repeat"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        if args.len() < 3 {
+            eprintln!("repeat: usage: repeat count command [arg ...]");
+            return 2;
+        }
+        let count: usize = match args[1].parse() {
+            Ok(count) => count,
+            Err(_) => {
+                eprintln!("repeat: {}: numeric argument required", args[1]);
+                return 2;
+            },
+        };
+        let line = args[2 ..].iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(" ");
+        io.flush();
+        rush.status = 0;
+        for _ in 0 .. count {
+            rush.execute(&line);
+        }
+        rush.status
+    }
+}
+
+pub struct Enable;
+
+impl Builtin for Enable {
+    fn name(&self) -> &'static str {
+        "enable"
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], io: &mut Io) -> c_int {
+        let mut disable = false;
+        let mut all = false;
+        let mut names = Vec::new();
+        for arg in &args[1 ..] {
+            match arg.as_str() {
+                "-n" => disable = true,
+                "-a" => all = true,
+                _ if arg.starts_with('-') => {
+                    eprintln!("enable: {}: invalid option", arg);
+                    return 2;
+                },
+                _ => names.push(arg.as_str()),
+            }
+        }
+        if names.is_empty() {
+            let mut states: Vec<_> = rush.builtins.keys().map(|&name| (name, true))
+                .chain(rush.disabled_builtins.keys().map(|&name| (name, false)))
+                .filter(|&(_, enabled)| all || enabled != disable)
+                .collect();
+            states.sort();
+            for (name, enabled) in states {
+                writeln!(io, "enable {}{}", if enabled { "" } else { "-n " }, name);
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for name in names {
+            let (from, to) = if disable {
+                (&mut rush.builtins, &mut rush.disabled_builtins)
+            } else {
+                (&mut rush.disabled_builtins, &mut rush.builtins)
+            };
+            if let Some((name, builtin)) = from.remove_entry(name) {
+                to.insert(name, builtin);
+            } else if !to.contains_key(name) {
+                eprintln!("enable: {}: not a shell builtin", name);
+                status = 1;
+            }
+        }
+        status
+    }
+}
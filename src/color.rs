@@ -0,0 +1,78 @@
+use libc::c_int;
+use shell::Rush;
+use std::fs;
+use std::sync::Mutex;
+use sys::isatty;
+
+const TERMINFO_DIRS: &[&str] = &["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"];
+const MAX_COLORS: usize = 13;
+
+static CACHE: Mutex<Option<(String, Option<i32>)>> = Mutex::new(None);
+
+fn read_number(bytes: &[u8]) -> i32 {
+    match *bytes {
+        [a, b] => i16::from_le_bytes([a, b]) as i32,
+        [a, b, c, d] => i32::from_le_bytes([a, b, c, d]),
+        _ => -1,
+    }
+}
+
+fn max_colors(data: &[u8]) -> Option<i32> {
+    let header: Vec<_> = data.get(.. 12)?.chunks(2).map(read_number).collect();
+    let size = match header[0] {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, numbers) = (header[1] as usize, header[2] as usize, header[3] as usize);
+    if numbers <= MAX_COLORS {
+        return Some(-1);
+    }
+    let start = (12 + names + bools).next_multiple_of(2) + MAX_COLORS * size;
+    data.get(start .. start + size).map(read_number)
+}
+
+impl Rush {
+    fn terminfo_dirs(&self) -> Vec<String> {
+        let mut dirs: Vec<_> = self.vars.get("TERMINFO").map(str::to_string).into_iter().collect();
+        dirs.extend(self.vars.get("HOME").map(|home| format!("{}/.terminfo", home)));
+        for dir in self.vars.get("TERMINFO_DIRS").unwrap_or("").split(':').filter(|dir| !dir.is_empty()) {
+            dirs.push(dir.to_owned());
+        }
+        dirs.extend(TERMINFO_DIRS.iter().map(|dir| dir.to_string()));
+        dirs
+    }
+
+    fn term_colors(&self, term: &str) -> Option<i32> {
+        let mut cache = CACHE.lock().unwrap();
+        if let Some((ref cached, colors)) = *cache {
+            if cached == term {
+                return colors;
+            }
+        }
+        let first = term.chars().next()?;
+        let colors = self.terminfo_dirs().iter()
+            .flat_map(|dir| vec![format!("{}/{}/{}", dir, first, term), format!("{}/{:x}/{}", dir, first as u32, term)])
+            .find_map(|path| fs::read(path).ok())
+            .and_then(|data| max_colors(&data));
+        *cache = Some((term.to_owned(), colors));
+        colors
+    }
+
+    pub fn use_color(&self, fd: c_int) -> bool {
+        let set = |name| self.vars.get(name).is_some_and(|value| !value.is_empty());
+        if set("NO_COLOR") {
+            return false;
+        }
+        if set("CLICOLOR_FORCE") && self.vars.get("CLICOLOR_FORCE") != Some("0") {
+            return true;
+        }
+        if self.vars.get("CLICOLOR") == Some("0") || !isatty(fd) {
+            return false;
+        }
+        match self.vars.get("TERM") {
+            None | Some("") | Some("dumb") => false,
+            Some(term) => self.term_colors(term).is_none_or(|colors| colors >= 8),
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use lexer::split_words;
+use std::env;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use sys::output_timeout;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+const SEPARATORS: &[&str] = &["|", "&", "&&", "||", ";"];
+const SPECIAL: &str = " \t\\|&<>$'\"`*?[]#;(){}!~";
+
+pub const BASH_COMPLETION: &str = "/usr/share/bash-completion/bash_completion";
+
+const BASH_LOAD: &str = r#"[ -f "$1" ] && . "$1" >/dev/null 2>&1
+complete -r
+. "$2" >/dev/null 2>&1
+complete -p"#;
+
+const BASH_CALL: &str = r#"[ -f "$1" ] && . "$1" >/dev/null 2>&1
+. "$2" >/dev/null 2>&1
+function=$3
+shift 3
+COMP_WORDS=("$@")
+COMP_TYPE=9
+COMP_KEY=9
+COMPREPLY=()
+"$function" "$1" "${COMP_WORDS[COMP_CWORD]}" "${COMP_WORDS[COMP_CWORD-1]}" >/dev/null 2>&1
+printf '%s\n' "${COMPREPLY[@]}""#;
+
+pub fn escape(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        if SPECIAL.contains(c) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn unquote(text: &str) -> String {
+    let mut result = String::new();
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('\\', Some('"')) | ('\\', None) => result.extend(chars.next()),
+            (c, _) => result.push(c),
+        }
+    }
+    result
+}
+
+pub fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            result.extend(chars.next());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+pub fn word_start(line: &[char], pos: usize) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, &c) in line[.. pos].iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if c.is_whitespace() {
+            start = i + 1;
+        } else {
+            escaped = c == '\\';
+        }
+    }
+    start
+}
+
+pub fn variable(word: &[char]) -> Option<(usize, String, bool)> {
+    let mut dollar = None;
+    let mut escaped = false;
+    for (i, &c) in word.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '$' {
+            dollar = Some(i);
+        }
+    }
+    let dollar = dollar?;
+    let rest = &word[dollar+1 ..];
+    let (braced, name) = match rest.first() {
+        Some(&'{') => (true, &rest[1 ..]),
+        _ => (false, rest),
+    };
+    if !name.iter().all(|&c| c == '_' || c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some((dollar, name.iter().collect(), braced))
+}
+
+pub fn command_words(line: &[char], start: usize) -> Vec<String> {
+    let before: String = line[.. start].iter().collect();
+    let mut words = Vec::new();
+    for word in split_words(&before) {
+        if SEPARATORS.contains(&word) {
+            words.clear();
+        } else {
+            words.push(unquote(word));
+        }
+    }
+    words
+}
+
+pub fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<_>, Vec<_>) = (a.chars().collect(), b.chars().collect());
+    let mut rows = vec![(0 ..= b.len()).collect::<Vec<_>>()];
+    for i in 1 ..= a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1 ..= b.len() {
+            let cost = if a[i-1] == b[j-1] { 0 } else { 1 };
+            row[j] = (rows[i-1][j] + 1).min(row[j-1] + 1).min(rows[i-1][j-1] + cost);
+            if i > 1 && j > 1 && a[i-1] == b[j-2] && a[i-2] == b[j-1] {
+                row[j] = row[j].min(rows[i-2][j-2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Matcher {
+    pub fuzzy: bool,
+    pub nocase: bool,
+}
+
+fn fold(text: &str) -> String {
+    text.chars().flat_map(char::to_lowercase).map(|c| if c == '_' { '-' } else { c }).collect()
+}
+
+impl Matcher {
+    fn score(&self, prefix: &str, name: &str) -> Option<(u8, usize)> {
+        if self.nocase {
+            let (prefix, name) = (fold(prefix), fold(name));
+            return Matcher { nocase: false, ..*self }.score(&prefix, &name);
+        }
+        if name.starts_with(prefix) {
+            return Some((0, 0));
+        }
+        if !self.fuzzy {
+            return None;
+        }
+        if let Some(pos) = name.find(prefix) {
+            return Some((1, pos));
+        }
+        let mut first = None;
+        let mut last = 0;
+        let mut chars = prefix.chars().peekable();
+        for (i, c) in name.chars().enumerate() {
+            if chars.peek() == Some(&c) {
+                chars.next();
+                first = first.or(Some(i));
+                last = i;
+            }
+        }
+        if chars.peek().is_some() {
+            return None;
+        }
+        Some((2, last - first.unwrap_or(0)))
+    }
+}
+
+struct Matches {
+    matches: Vec<(u8, usize, String)>,
+}
+
+impl Matches {
+    fn new() -> Self {
+        Self {
+            matches: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, score: (u8, usize), candidate: String) {
+        self.matches.push((score.0, score.1, candidate));
+    }
+
+    fn contains(&self, candidate: &str) -> bool {
+        self.matches.iter().any(|m| m.2 == candidate)
+    }
+
+    fn ranked(mut self) -> Vec<String> {
+        let best = self.matches.iter().map(|m| m.0).min().unwrap_or(0);
+        self.matches.retain(|m| m.0 == best);
+        self.matches.sort();
+        self.matches.dedup_by(|a, b| a.2 == b.2);
+        self.matches.into_iter().map(|m| m.2).collect()
+    }
+}
+
+pub fn words<I: IntoIterator<Item = String>>(matcher: Matcher, prefix: &str, names: I) -> Vec<String> {
+    let mut matches = Matches::new();
+    for name in names {
+        if let Some(score) = matcher.score(prefix, &name) {
+            matches.push(score, escape(&name));
+        }
+    }
+    matches.ranked()
+}
+
+pub fn commands(matcher: Matcher, prefix: &str, builtins: &[&str], path: &[String]) -> Vec<String> {
+    let mut matches = Matches::new();
+    for name in builtins.iter().cloned().chain(path.iter().map(String::as_str)) {
+        if let Some(score) = matcher.score(prefix, name) {
+            let name = escape(name);
+            if !matches.contains(&name) {
+                matches.push(score, name);
+            }
+        }
+    }
+    matches.ranked()
+}
+
+pub fn filenames(matcher: Matcher, word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(slash) => (&word[.. slash+1], &word[slash+1 ..]),
+        None => ("", word),
+    };
+    let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut matches = Matches::new();
+    for entry in entries.filter_map(Result::ok) {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if name.starts_with('.') && !prefix.starts_with('.') {
+            continue;
+        }
+        let score = match matcher.score(prefix, &name) {
+            Some(score) => score,
+            None => continue,
+        };
+        let mut candidate = escape(&format!("{}{}", dir, name));
+        if fs::metadata(entry.path()).map(|meta| meta.is_dir()).unwrap_or(false) {
+            candidate.push('/');
+        }
+        matches.push(score, candidate);
+    }
+    matches.ranked()
+}
+
+fn known_hosts() -> Vec<String> {
+    let home = match env::var("HOME") {
+        Ok(home) => home,
+        Err(_) => return Vec::new(),
+    };
+    let mut hosts = Vec::new();
+    if let Ok(config) = fs::read_to_string(format!("{}/.ssh/config", home)) {
+        for line in config.lines() {
+            let mut words = line.split_whitespace();
+            if words.next().is_some_and(|word| word.eq_ignore_ascii_case("host")) {
+                hosts.extend(words.filter(|host| !host.contains(['*', '?', '!'])).map(str::to_string));
+            }
+        }
+    }
+    if let Ok(known) = fs::read_to_string(format!("{}/.ssh/known_hosts", home)) {
+        for line in known.lines() {
+            let field = match line.split_whitespace().next() {
+                Some(field) if !field.starts_with('#') && !field.starts_with('|') => field,
+                _ => continue,
+            };
+            let field = if field.starts_with('@') {
+                match line.split_whitespace().nth(1) {
+                    Some(field) => field,
+                    None => continue,
+                }
+            } else {
+                field
+            };
+            for host in field.split(',') {
+                let host = match host.strip_prefix('[') {
+                    Some(rest) => rest.split(']').next().unwrap_or(rest),
+                    None => host,
+                };
+                hosts.push(host.to_string());
+            }
+        }
+    }
+    hosts
+}
+
+pub fn hostnames(matcher: Matcher, word: &str, suffix: &str) -> Vec<String> {
+    let (user, prefix) = match word.find('@') {
+        Some(at) => (&word[.. at+1], &word[at+1 ..]),
+        None => ("", word),
+    };
+    let hosts = known_hosts().into_iter().map(|host| format!("{}{}", host, suffix));
+    words(matcher, prefix, hosts).into_iter().map(|host| format!("{}{}", escape(user), host)).collect()
+}
+
+pub fn directories(matcher: Matcher, word: &str) -> Vec<String> {
+    filenames(matcher, word).into_iter().filter(|candidate| candidate.ends_with('/')).collect()
+}
+
+#[derive(Clone, Default)]
+pub struct Spec {
+    pub words: Option<String>,
+    pub command: Option<String>,
+    pub files: bool,
+    pub dirs: bool,
+    pub commands: bool,
+    pub builtins: bool,
+    pub vars: bool,
+    pub function: Option<String>,
+    pub script: Option<String>,
+    pub default: bool,
+}
+
+impl Spec {
+    pub fn parse(args: &[String]) -> Result<(Self, usize), String> {
+        let mut spec = Self::default();
+        let mut i = 0;
+        while i < args.len() && args[i].starts_with('-') && args[i].len() > 1 {
+            if args[i] == "--" {
+                i += 1;
+                break;
+            }
+            let flags: Vec<_> = args[i][1 ..].chars().collect();
+            for (j, &flag) in flags.iter().enumerate() {
+                match flag {
+                    'f' => spec.files = true,
+                    'd' => spec.dirs = true,
+                    'c' => spec.commands = true,
+                    'b' => spec.builtins = true,
+                    'v' => spec.vars = true,
+                    'W' | 'C' | 'F' | 'A' | 'o' => {
+                        let value = if j + 1 < flags.len() {
+                            flags[j+1 ..].iter().collect()
+                        } else {
+                            i += 1;
+                            match args.get(i) {
+                                Some(value) => value.clone(),
+                                None => return Err(format!("-{}: option requires an argument", flag)),
+                            }
+                        };
+                        match flag {
+                            'W' => spec.words = Some(value),
+                            'C' => spec.command = Some(value),
+                            'F' => spec.function = Some(value),
+                            'o' => spec.default |= value == "default" || value == "bashdefault",
+                            _ => match value.as_str() {
+                                "file" => spec.files = true,
+                                "directory" => spec.dirs = true,
+                                "command" => spec.commands = true,
+                                "builtin" => spec.builtins = true,
+                                "variable" => spec.vars = true,
+                                _ => return Err(format!("{}: invalid action name", value)),
+                            },
+                        }
+                        break;
+                    },
+                    _ => return Err(format!("-{}: invalid option", flag)),
+                }
+            }
+            i += 1;
+        }
+        Ok((spec, i))
+    }
+
+    pub fn to_args(&self) -> String {
+        let mut args = Vec::new();
+        for &(on, flag) in &[(self.files, "-f"), (self.dirs, "-d"), (self.commands, "-c"),
+                             (self.builtins, "-b"), (self.vars, "-v")] {
+            if on {
+                args.push(flag.to_string());
+            }
+        }
+        if let Some(ref words) = self.words {
+            args.push(format!("-W {}", escape(words)));
+        }
+        if let Some(ref command) = self.command {
+            args.push(format!("-C {}", escape(command)));
+        }
+        if self.default {
+            args.push("-o default".to_string());
+        }
+        if let Some(ref function) = self.function {
+            args.push(format!("-F {}", escape(function)));
+        }
+        args.join(" ")
+    }
+}
+
+pub type Specs = Vec<(Spec, Vec<String>)>;
+
+pub fn load_bash(lib: &str, path: &str) -> Result<(Specs, Vec<String>), String> {
+    let output = Command::new("bash")
+        .args(["-c", BASH_LOAD, "rush", lib, path])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|error| format!("bash: {}", error))?;
+    let (mut specs, mut skipped) = (Vec::new(), Vec::new());
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let args: Vec<_> = split_words(line).into_iter().skip(1).map(unquote).collect();
+        match Spec::parse(&args) {
+            Ok((mut spec, i)) if i < args.len() => {
+                spec.script = Some(path.to_string());
+                specs.push((spec, args[i ..].to_vec()));
+            },
+            Ok(_) => {},
+            Err(error) => skipped.push(format!("{}: {}", args.last().map_or("", String::as_str), error)),
+        }
+    }
+    if specs.is_empty() && skipped.is_empty() {
+        return Err(format!("{}: no completions defined", path));
+    }
+    Ok((specs, skipped))
+}
+
+pub fn bash_function(lib: &str, script: &str, function: &str, words: &[String], cword: usize) -> Vec<String> {
+    let line = words.join(" ");
+    let output = output_timeout(Command::new("bash")
+        .args(["-c", BASH_CALL, "rush", lib, script, function])
+        .args(words)
+        .env("COMP_LINE", &line)
+        .env("COMP_POINT", line.len().to_string())
+        .env("COMP_CWORD", cword.to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null()), TIMEOUT);
+    match output {
+        Some((_, output)) => String::from_utf8_lossy(&output).lines()
+            .map(|line| line.trim_end_matches(' '))
+            .filter(|line| !line.is_empty())
+            .map(escape)
+            .collect(),
+        None => Vec::new(),
+    }
+}
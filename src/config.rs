@@ -0,0 +1,301 @@
+use expand::expand;
+use lexer::is_name;
+use shell::{Rush, OPTIONS};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use theme;
+
+const TABLES: &[&str] = &["options", "aliases", "prompt", "keybindings", "history", "env"];
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match *self {
+            Value::Bool(_) => "boolean",
+            Value::Int(_) => "integer",
+            Value::Str(_) => "string",
+            Value::Array(_) => "array",
+        }
+    }
+}
+
+fn is_bare(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+fn parse_basic(s: &str) -> Result<(String, &str), String> {
+    let mut result = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((result, &s[i+1 ..])),
+            '\\' => {
+                let escape = chars.next().map(|(_, c)| c);
+                let unescaped = match escape {
+                    Some('b') => '\x08',
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('f') => '\x0c',
+                    Some('r') => '\r',
+                    Some('e') => '\x1b',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some(u @ ('u' | 'U')) => {
+                        let len = if u == 'u' { 4 } else { 8 };
+                        let hex: String = (0 .. len).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) if hex.len() == len => c,
+                            _ => return Err(format!("\\{}{}: invalid unicode escape", u, hex)),
+                        }
+                    },
+                    Some(c) => return Err(format!("\\{}: invalid escape sequence", c)),
+                    None => break,
+                };
+                result.push(unescaped);
+            },
+            c => result.push(c),
+        }
+    }
+    Err("unterminated string".to_owned())
+}
+
+fn parse_string(s: &str) -> Result<(String, &str), String> {
+    if s.starts_with("\"\"\"") || s.starts_with("'''") {
+        return Err("multi-line strings are not supported".to_owned());
+    }
+    if s.starts_with('"') {
+        return parse_basic(s);
+    }
+    match s[1 ..].find('\'') {
+        Some(end) => Ok((s[1 .. end+1].to_owned(), &s[end+2 ..])),
+        None => Err("unterminated string".to_owned()),
+    }
+}
+
+fn parse_key(s: &str) -> Result<(String, &str), String> {
+    if s.starts_with(['"', '\'']) {
+        return parse_string(s);
+    }
+    let end = s.find(|c| !is_bare(c)).unwrap_or(s.len());
+    if end == 0 {
+        return Err(format!("{}: invalid key", s));
+    }
+    Ok((s[.. end].to_owned(), &s[end ..]))
+}
+
+fn parse_value(s: &str) -> Result<(Value, &str), String> {
+    if s.starts_with(['"', '\'']) {
+        return parse_string(s).map(|(string, rest)| (Value::Str(string), rest));
+    }
+    if let Some(mut rest) = s.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), rest));
+            }
+            if rest.is_empty() {
+                return Err("multi-line arrays are not supported".to_owned());
+            }
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected `,` or `]` in array".to_owned());
+            }
+        }
+    }
+    let end = s.find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#').unwrap_or(s.len());
+    let (word, rest) = s.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match word.replace('_', "").parse() {
+            Ok(n) => Value::Int(n),
+            Err(_) if word.is_empty() => return Err("missing value".to_owned()),
+            Err(_) => return Err(format!("{}: unsupported value", word)),
+        },
+    };
+    Ok((value, rest))
+}
+
+fn end_of_line(rest: &str) -> Result<(), String> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("{}: unexpected text after value", rest))
+    }
+}
+
+enum Line {
+    Table(String),
+    Entry(String, Value),
+}
+
+fn parse_line(line: &str) -> Result<Option<Line>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    if line.starts_with("[[") {
+        return Err("arrays of tables are not supported".to_owned());
+    }
+    if let Some(header) = line.strip_prefix('[') {
+        let (name, rest) = parse_key(header.trim_start())?;
+        let rest = rest.trim_start().strip_prefix(']').ok_or_else(|| "expected `]` after table name".to_owned())?;
+        end_of_line(rest)?;
+        return Ok(Some(Line::Table(name)));
+    }
+    let (key, rest) = parse_key(line)?;
+    let rest = rest.trim_start();
+    if rest.starts_with('.') {
+        return Err(format!("{}: dotted keys are not supported", key));
+    }
+    let rest = rest.strip_prefix('=').ok_or_else(|| format!("{}: expected `=` after key", key))?;
+    let (value, rest) = parse_value(rest.trim_start())?;
+    end_of_line(rest)?;
+    Ok(Some(Line::Entry(key, value)))
+}
+
+fn string_list(value: &Value, separator: &str) -> Option<String> {
+    match *value {
+        Value::Str(ref string) => Some(string.clone()),
+        Value::Array(ref values) => values.iter()
+            .map(|value| match *value {
+                Value::Str(ref string) => Some(string.as_str()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|strings| strings.join(separator)),
+        _ => None,
+    }
+}
+
+impl Rush {
+    fn apply_config(&mut self, table: &str, key: &str, value: Value) -> Result<(), String> {
+        let expected = |expected: &str, value: &Value| format!("{}: expected {}, found {}", key, expected, value.kind());
+        match (table, key, value) {
+            ("options", _, Value::Bool(on)) if OPTIONS.iter().any(|opt| opt.0 == key) => {
+                self.options.set(key, on);
+            },
+            ("options", _, value) if OPTIONS.iter().any(|opt| opt.0 == key) => return Err(expected("boolean", &value)),
+            ("options", _, _) => return Err(format!("{}: unknown option", key)),
+            ("aliases", _, Value::Str(value)) => {
+                self.aliases.insert(key.to_owned(), value);
+            },
+            ("aliases", _, value) => return Err(expected("string", &value)),
+            ("prompt", "theme", Value::Str(name)) => match theme::find(&name) {
+                Some(_) => self.vars.set("RUSH_THEME", &name),
+                None => return Err(format!("{}: unknown theme", name)),
+            },
+            ("prompt", "ps1", Value::Str(value)) => self.vars.set("PS1", &value),
+            ("prompt", "rprompt", Value::Str(value)) => self.vars.set("RPROMPT", &value),
+            ("prompt", "theme" | "ps1" | "rprompt", value) => return Err(expected("string", &value)),
+            ("keybindings", _, Value::Str(function)) => {
+                self.keymap.bind(&format!("\"{}\": {}", key.replace('"', "\\\""), function))?;
+            },
+            ("keybindings", _, value) => return Err(expected("string", &value)),
+            ("history", "size" | "filesize", Value::Int(n)) if n >= 0 => {
+                self.vars.set(if key == "size" { "HISTSIZE" } else { "HISTFILESIZE" }, &n.to_string());
+            },
+            ("history", "size" | "filesize", value) => return Err(expected("non-negative integer", &value)),
+            ("history", "file", Value::Str(path)) => self.vars.set("HISTFILE", &path),
+            ("history", "timeformat", Value::Str(format)) => self.vars.set("HISTTIMEFORMAT", &format),
+            ("history", "file" | "timeformat", value) => return Err(expected("string", &value)),
+            ("history", "secrets", value) => match string_list(&value, "\n") {
+                Some(list) => self.vars.set("HISTSECRETS", &list),
+                None => return Err(expected("string or array of strings", &value)),
+            },
+            ("history", "control" | "ignore", value) => match string_list(&value, ":") {
+                Some(list) => self.vars.set(if key == "control" { "HISTCONTROL" } else { "HISTIGNORE" }, &list),
+                None => return Err(expected("string or array of strings", &value)),
+            },
+            ("history", "share", Value::Bool(on)) => {
+                self.options.set("sharehistory", on);
+            },
+            ("history", "share", value) => return Err(expected("boolean", &value)),
+            ("env", _, Value::Str(value)) if is_name(key) => {
+                let value = expand(&value, self).map_err(|error| format!("{}: {}", key, error))?;
+                env::set_var(key, &value);
+                self.vars.set(key, &value);
+            },
+            ("env", _, Value::Str(_)) => return Err(format!("{}: not a valid identifier", key)),
+            ("env", _, value) => return Err(expected("string", &value)),
+            ("", _, _) => return Err(format!("{}: key outside of a table", key)),
+            _ => return Err(format!("{}: unknown key in [{}]", key, table)),
+        }
+        Ok(())
+    }
+
+    pub fn read_config(&mut self, path: &str) -> io::Result<Vec<(usize, String)>> {
+        let text = fs::read_to_string(path)?;
+        let mut errors = Vec::new();
+        let mut table = Some(String::new());
+        let mut seen = HashSet::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let result = parse_line(line).and_then(|line| match (line, &table) {
+                (Some(Line::Table(name)), _) => {
+                    if !TABLES.contains(&name.as_str()) {
+                        table = None;
+                        return Err(format!("[{}]: unknown table", name));
+                    }
+                    table = Some(name);
+                    Ok(())
+                },
+                (Some(Line::Entry(key, value)), Some(table)) => {
+                    if !seen.insert((table.clone(), key.clone())) {
+                        return Err(format!("{}: duplicate key", key));
+                    }
+                    self.apply_config(table, &key, value)
+                },
+                _ => Ok(()),
+            });
+            if let Err(error) = result {
+                errors.push((lineno+1, error));
+            }
+        }
+        Ok(errors)
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.vars.get("RUSH_PROFILE").filter(|profile| !profile.is_empty() && !profile.contains('/'))
+    }
+
+    fn load_config_file(&mut self, path: &str, required: bool) {
+        match self.read_config(path) {
+            Ok(errors) => {
+                for (lineno, error) in errors {
+                    eprintln!("rush: {}: line {}: {}", path, lineno, error);
+                }
+            },
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound && !required => {},
+            Err(error) => eprintln!("rush: {}: {}", path, error),
+        }
+    }
+
+    pub fn load_config(&mut self) {
+        let dir = match self.config_dir() {
+            Some(dir) => dir,
+            None => return,
+        };
+        self.load_config_file(&format!("{}/config.toml", dir), false);
+        match self.profile().map(str::to_string) {
+            Some(profile) => self.load_config_file(&format!("{}/profiles/{}.toml", dir, profile), true),
+            None => if let Some(profile) = self.vars.get("RUSH_PROFILE") {
+                eprintln!("rush: {}: invalid profile name", profile);
+            },
+        }
+    }
+}
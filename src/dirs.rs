@@ -0,0 +1,142 @@
+use frecency;
+use libc::c_int;
+use shell::Rush;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use sys::{chdir, getcwd, home_dir, time};
+
+pub fn normalize(path: &str) -> String {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {},
+            ".." => {
+                parts.pop();
+            },
+            _ => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn one_edit(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<_>, Vec<_>) = (a.chars().collect(), b.chars().collect());
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix ..], &b[prefix ..]);
+    match (a.len(), b.len()) {
+        (0, 0) => false,
+        (x, y) if x == y + 1 => a[1 ..] == *b,
+        (x, y) if x + 1 == y => *a == b[1 ..],
+        (x, y) if x == y => a[1 ..] == b[1 ..] || x >= 2 && a[0] == b[1] && a[1] == b[0] && a[2 ..] == b[2 ..],
+        _ => false,
+    }
+}
+
+pub fn spell_correct(path: &str) -> Option<String> {
+    let mut result = if path.starts_with('/') { "/".to_string() } else { String::new() };
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        let dir = if result.is_empty() { "." } else { &result };
+        let part = if Path::new(dir).join(part).is_dir() {
+            part.to_string()
+        } else {
+            fs::read_dir(dir).ok()?
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .find(|name| one_edit(part, name))?
+        };
+        if !result.is_empty() && !result.ends_with('/') {
+            result.push('/');
+        }
+        result.push_str(&part);
+    }
+    Some(result)
+}
+
+impl Rush {
+    pub fn dirfile(&self) -> Option<String> {
+        match self.vars.get("RUSH_DIRFILE") {
+            Some(path) => Some(path.to_owned()),
+            None => self.data_file("dirs", Some(".rush_dirs")),
+        }
+    }
+
+    pub fn tilde(&self, path: String) -> String {
+        let home = self.vars.get("HOME").map(|home| ("", home));
+        let named = self.named_dirs.iter().map(|(name, dir)| (name.as_str(), dir.as_str()));
+        let best = home.into_iter().chain(named)
+            .filter(|&(_, dir)| !dir.is_empty() && dir != "/")
+            .filter(|&(_, dir)| path == dir || path.starts_with(&format!("{}/", dir)))
+            .max_by_key(|&(name, dir)| (dir.len(), name.is_empty()));
+        match best {
+            Some((name, dir)) => format!("~{}{}", name, &path[dir.len() ..]),
+            None => path,
+        }
+    }
+
+    pub fn tilde_dir(&self, name: &str) -> Option<String> {
+        if name.is_empty() {
+            return self.vars.get("HOME").map(str::to_string);
+        }
+        match self.named_dirs.get(name) {
+            Some(dir) if !self.options.posix => Some(dir.clone()),
+            _ => home_dir(name),
+        }
+    }
+
+    pub fn dir_stack(&self) -> Vec<String> {
+        let mut stack = vec![self.cwd.clone()];
+        stack.extend(self.dirs.iter().cloned());
+        stack
+    }
+
+    pub fn stack_index(&self, arg: &str) -> Option<usize> {
+        let len = self.dirs.len() + 1;
+        let n = arg.get(1 ..)?.parse::<usize>().ok()?;
+        match arg.chars().next() {
+            Some('+') if n < len => Some(n),
+            Some('-') if n < len => Some(len - 1 - n),
+            _ => None,
+        }
+    }
+
+    pub fn auto_pushd(&mut self, old: String) {
+        if self.options.autopushd && old != self.cwd {
+            self.dirs.insert(0, old);
+        }
+    }
+
+    pub fn chdir(&mut self, dir: &str, physical: bool) -> c_int {
+        let logical = if dir.starts_with('/') {
+            normalize(dir)
+        } else {
+            normalize(&format!("{}/{}", self.cwd, dir))
+        };
+        let cwd = if !physical && chdir(&logical) == 0 {
+            logical
+        } else if chdir(dir) == 0 {
+            getcwd()
+        } else {
+            return -1;
+        };
+        let old = mem::replace(&mut self.cwd, cwd);
+        self.vars.set("OLDPWD", &old);
+        let cwd = self.cwd.clone();
+        self.vars.set("PWD", &cwd);
+        if let Some(file) = self.dirfile() {
+            if self.vars.get("HOME") != Some(&cwd) {
+                if let Err(error) = frecency::visit(&file, &cwd, time()) {
+                    if !self.dirfile_failed {
+                        eprintln!("rush: {}: {}", file, error);
+                    }
+                    self.dirfile_failed = true;
+                }
+            }
+        }
+        self.update_dir_env(false);
+        self.report_cwd();
+        self.run_hooks("on_chdir");
+        0
+    }
+}
@@ -0,0 +1,1549 @@
+use highlight::Style;
+use keymap::{Action, Keymap};
+use libc::{self, c_int};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, stdout, Write};
+use std::mem;
+use std::process::Command;
+use sys::{create_temp, retry};
+
+const KILL_RING_SIZE: usize = 10;
+const PROMPT_POLL: c_int = 50;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    CtrlX(char),
+    Alt(char),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    Delete,
+    Esc,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Paste(String),
+    Unknown,
+}
+
+struct RawMode {
+    orig: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        unsafe {
+            let mut orig = mem::zeroed();
+            if libc::tcgetattr(0, &mut orig) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = orig;
+            raw.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+            raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+            raw.c_cflag |= libc::CS8;
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if retry(|| libc::tcsetattr(0, libc::TCSADRAIN, &raw)) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            print!("\x1b[?2004h");
+            Ok(Self {
+                orig,
+            })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?2004l");
+        let _ = stdout().flush();
+        retry(|| unsafe {
+            libc::tcsetattr(0, libc::TCSADRAIN, &self.orig)
+        });
+    }
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut byte = 0u8;
+    let ret = retry(|| unsafe {
+        libc::read(0, &mut byte as *mut u8 as *mut libc::c_void, 1)
+    });
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(None),
+        _ => Ok(Some(byte)),
+    }
+}
+
+fn pending(timeout: c_int) -> bool {
+    let mut fds = libc::pollfd {
+        fd: 0,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    retry(|| unsafe {
+        libc::poll(&mut fds, 1, timeout)
+    }) > 0
+}
+
+fn read_char(first: u8) -> io::Result<Option<char>> {
+    let len = match first {
+        0x00 ..= 0x7f => 1,
+        0xc0 ..= 0xdf => 2,
+        0xe0 ..= 0xef => 3,
+        0xf0 ..= 0xf7 => 4,
+        _ => return Ok(None),
+    };
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => return Ok(None),
+        }
+    }
+    Ok(String::from_utf8(bytes).ok().and_then(|s| s.chars().next()))
+}
+
+fn read_escape() -> io::Result<Key> {
+    if !pending(50) {
+        return Ok(Key::Esc);
+    }
+    let intro = match read_byte()? {
+        Some(byte) => byte,
+        None => return Ok(Key::Esc),
+    };
+    if intro != b'[' && intro != b'O' {
+        return Ok(match read_char(intro)? {
+            Some(c) => Key::Alt(c),
+            None => Key::Unknown,
+        });
+    }
+    let mut seq = Vec::new();
+    loop {
+        match read_byte()? {
+            Some(byte) => {
+                seq.push(byte);
+                if (0x40 ..= 0x7e).contains(&byte) {
+                    break;
+                }
+            },
+            None => return Ok(Key::Unknown),
+        }
+    }
+    if seq == b"200~" {
+        return Ok(Key::Paste(read_paste()?));
+    }
+    Ok(escape_key(&seq))
+}
+
+pub fn escape_key(seq: &[u8]) -> Key {
+    match seq {
+        b"A" => Key::Up,
+        b"B" => Key::Down,
+        b"C" => Key::Right,
+        b"1;3C" | b"1;9C" => Key::Alt('f'),
+        b"D" => Key::Left,
+        b"H" | b"1~" | b"7~" => Key::Home,
+        b"F" | b"4~" | b"8~" => Key::End,
+        b"3~" => Key::Delete,
+        b"Z" => Key::BackTab,
+        _ => Key::Unknown,
+    }
+}
+
+pub fn control_key(byte: u8) -> Key {
+    match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 ..= 0x1a => Key::Ctrl((byte + 0x60) as char),
+        _ => Key::Ctrl((byte + 0x40) as char),
+    }
+}
+
+fn read_paste() -> io::Result<String> {
+    let mut bytes = Vec::new();
+    while let Some(byte) = read_byte()? {
+        bytes.push(byte);
+        if bytes.ends_with(b"\x1b[201~") {
+            bytes.truncate(bytes.len() - 6);
+            break;
+        }
+    }
+    let text = String::from_utf8_lossy(&bytes).replace("\r\n", "\n").replace('\r', "\n");
+    Ok(text)
+}
+
+fn width(c: char) -> usize {
+    match c as u32 {
+        0x00 ..= 0x1f | 0x7f => 2,
+        0x0300 ..= 0x036f | 0x200b ..= 0x200f | 0x20d0 ..= 0x20ff | 0xfe00 ..= 0xfe0f | 0xfe20 ..= 0xfe2f => 0,
+        0x1100 ..= 0x115f | 0x2e80 ..= 0x303e | 0x3041 ..= 0x33ff | 0x3400 ..= 0x4dbf | 0x4e00 ..= 0x9fff |
+        0xa000 ..= 0xa4cf | 0xac00 ..= 0xd7a3 | 0xf900 ..= 0xfaff | 0xfe30 ..= 0xfe4f | 0xff00 ..= 0xff60 |
+        0xffe0 ..= 0xffe6 | 0x1f300 ..= 0x1f64f | 0x1f900 ..= 0x1f9ff | 0x20000 ..= 0x2fffd | 0x30000 ..= 0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+fn visible_width(text: &str) -> usize {
+    let mut visible = true;
+    let mut total = 0;
+    for c in text.chars() {
+        match c {
+            '\x01' => visible = false,
+            '\x02' => visible = true,
+            _ if visible => total += width(c),
+            _ => {},
+        }
+    }
+    total
+}
+
+fn columns() -> usize {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    let ret = unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) };
+    if ret == -1 || size.ws_col == 0 {
+        80
+    } else {
+        size.ws_col as usize
+    }
+}
+
+fn advance(row: &mut usize, col: &mut usize, width: usize, cols: usize) {
+    if *col + width > cols {
+        *row += 1;
+        *col = 0;
+    }
+    *col += width;
+}
+
+fn prompt_end(prompt: &str, cols: usize) -> (usize, usize) {
+    let (mut row, mut col) = (0, 0);
+    let mut visible = true;
+    for c in prompt.chars() {
+        match c {
+            '\x01' => visible = false,
+            '\x02' => visible = true,
+            '\n' => {
+                row += 1;
+                col = 0;
+            },
+            _ if visible => advance(&mut row, &mut col, width(c), cols),
+            _ => {},
+        }
+    }
+    (row, col)
+}
+
+fn push_cell(out: &mut String, c: char, style: Style, current: &mut Style) {
+    if style != *current {
+        if *current != Style::Plain {
+            out.push_str("\x1b[0m");
+        }
+        if style != Style::Plain {
+            out.push_str(&format!("\x1b[{}m", style.sgr()));
+        }
+        *current = style;
+    }
+    if c.is_ascii_control() {
+        out.push('^');
+        out.push(((c as u8) ^ 0x40) as char);
+    } else {
+        out.push(c);
+    }
+}
+
+fn move_rows(out: &mut String, from: usize, to: usize) {
+    if to < from {
+        out.push_str(&format!("\x1b[{}A", from - to));
+    } else if to > from {
+        out.push_str(&format!("\x1b[{}B", to - from));
+    }
+}
+
+fn read_key() -> io::Result<Option<Key>> {
+    let byte = match read_byte()? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+    let key = match byte {
+        0x1b => read_escape()?,
+        0x00 ..= 0x1f | 0x7f => control_key(byte),
+        _ => match read_char(byte)? {
+            Some(c) => Key::Char(c),
+            None => Key::Unknown,
+        },
+    };
+    Ok(Some(key))
+}
+
+pub trait Complete {
+    fn complete(&self, line: &[char], pos: usize) -> (usize, Vec<String>);
+    fn search_history(&self, query: &str, before: usize) -> Option<(usize, String)>;
+    fn prompt_pending(&self) -> bool;
+    fn highlight(&self, line: &str) -> Vec<Style>;
+    fn poll_prompt(&self) -> Option<(String, String)>;
+    fn external_editor(&self) -> Option<String>;
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = candidates[0].clone();
+    for candidate in &candidates[1 ..] {
+        let len = prefix.chars().zip(candidate.chars())
+            .take_while(|&(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        prefix.truncate(len);
+    }
+    if prefix.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1 {
+        prefix.pop();
+    }
+    prefix
+}
+
+fn display_name(candidate: &str) -> &str {
+    let trimmed = candidate.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(slash) => &candidate[slash+1 ..],
+        None => candidate,
+    }
+}
+
+fn menu_lines(candidates: &[String], selected: usize, cols: usize) -> Vec<String> {
+    let names: Vec<_> = candidates.iter().map(|candidate| display_name(candidate)).collect();
+    let widths: Vec<usize> = names.iter().map(|name| name.chars().map(width).sum()).collect();
+    let cell = widths.iter().max().unwrap_or(&0) + 2;
+    let per_row = (cols / cell).max(1);
+    let rows = names.len().div_ceil(per_row);
+    let mut lines = Vec::new();
+    for row in 0 .. rows {
+        let mut line = String::new();
+        for index in (row .. names.len()).step_by(rows) {
+            if index == selected {
+                line.push_str(&format!("\x1b[7m{}\x1b[0m", names[index]));
+            } else {
+                line.push_str(names[index]);
+            }
+            if index + rows < names.len() {
+                line.push_str(&" ".repeat(cell - widths[index]));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+struct Drawn {
+    prompt: String,
+    rprompt: String,
+    text: Vec<(char, Style)>,
+    cols: usize,
+}
+
+pub struct Editor {
+    buf: Vec<char>,
+    pos: usize,
+    hisno: usize,
+    current: String,
+    vi: bool,
+    insert: bool,
+    register: String,
+    query: String,
+    kill_ring: VecDeque<String>,
+    undo: Vec<(Vec<char>, usize)>,
+    row: usize,
+    rows: usize,
+    rprompt: String,
+    drawn: Option<Drawn>,
+    autosuggest: bool,
+    suggestion: Vec<char>,
+    highlight: bool,
+    styles: Vec<Style>,
+    styled: Vec<char>,
+    keymap: Keymap,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            hisno: 0,
+            current: String::new(),
+            vi: false,
+            insert: true,
+            register: String::new(),
+            query: String::new(),
+            kill_ring: VecDeque::new(),
+            undo: Vec::new(),
+            row: 0,
+            rows: 0,
+            rprompt: String::new(),
+            drawn: None,
+            autosuggest: false,
+            suggestion: Vec::new(),
+            highlight: false,
+            styles: Vec::new(),
+            styled: Vec::new(),
+            keymap: Keymap::new(),
+        }
+    }
+
+    fn line(&self) -> String {
+        self.buf.iter().collect()
+    }
+
+    fn set_line(&mut self, line: &str) {
+        self.buf = line.chars().collect();
+        self.pos = self.buf.len();
+    }
+
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.buf.insert(self.pos, c);
+            self.pos += 1;
+        }
+    }
+
+    fn kill(&mut self, start: usize, end: usize) -> String {
+        self.pos = start;
+        self.buf.drain(start .. end).collect()
+    }
+
+    fn record(&mut self, before: (Vec<char>, usize), merge: bool) {
+        if before.0 != self.buf && !merge {
+            self.undo.push(before);
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some((buf, pos)) = self.undo.pop() {
+            self.buf = buf;
+            self.pos = pos.min(self.buf.len());
+        }
+    }
+
+    fn edit_external(&mut self, editor: &str) -> io::Result<()> {
+        let (path, mut file) = create_temp(&env::temp_dir(), "rush-edit-", ".sh")?;
+        if let Err(error) = writeln!(file, "{}", self.line()) {
+            let _ = fs::remove_file(&path);
+            return Err(error);
+        }
+        drop(file);
+        let mut words = editor.split_whitespace();
+        let status = Command::new(words.next().unwrap_or("vi")).args(words).arg(&path).status();
+        let text = fs::read_to_string(&path);
+        fs::remove_file(&path)?;
+        if !status?.success() {
+            return Ok(());
+        }
+        self.set_line(text?.trim_end_matches('\n'));
+        Ok(())
+    }
+
+    fn complete(&mut self, prompt: &str, start: usize, candidates: &[String], menu: bool) -> io::Result<Option<Key>> {
+        if candidates.is_empty() {
+            self.write("\x07")?;
+            return Ok(None);
+        }
+        let end = self.pos;
+        let mut prefix = common_prefix(candidates);
+        if candidates.len() == 1 {
+            if !prefix.ends_with('/') && !prefix.ends_with(':') {
+                prefix.push(' ');
+            }
+        } else if prefix.chars().count() <= end - start {
+            if menu {
+                return self.menu(prompt, start, candidates);
+            }
+            return Ok(None);
+        }
+        self.kill(start, end);
+        self.insert(&prefix);
+        Ok(None)
+    }
+
+    fn menu(&mut self, prompt: &str, start: usize, candidates: &[String]) -> io::Result<Option<Key>> {
+        let original: String = self.buf[start .. self.pos].iter().collect();
+        let mut selected = 0;
+        loop {
+            let end = self.pos;
+            self.kill(start, end);
+            self.insert(&candidates[selected]);
+            let footer = menu_lines(candidates, selected, columns());
+            let (row, rows) = self.draw(prompt, "", &self.cells(false), self.pos, &footer)?;
+            self.row = row;
+            self.rows = rows;
+            self.drawn = None;
+            let key = match read_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            match key {
+                Key::Tab | Key::Down | Key::Right => selected = (selected + 1) % candidates.len(),
+                Key::BackTab | Key::Up | Key::Left => {
+                    selected = (selected + candidates.len() - 1) % candidates.len();
+                },
+                Key::Ctrl('g') | Key::Esc => {
+                    let end = self.pos;
+                    self.kill(start, end);
+                    self.insert(&original);
+                    return Ok(None);
+                },
+                Key::Enter => return Ok(None),
+                key => return Ok(Some(key)),
+            }
+        }
+    }
+
+    fn kill_to_ring(&mut self, start: usize, end: usize, append: bool) {
+        let backward = end == self.pos && start < end;
+        let text = self.kill(start, end);
+        if text.is_empty() {
+            return;
+        }
+        match self.kill_ring.front_mut() {
+            Some(last) if append => {
+                if backward {
+                    last.insert_str(0, &text);
+                } else {
+                    last.push_str(&text);
+                }
+                return;
+            },
+            _ => {},
+        }
+        self.kill_ring.push_front(text);
+        self.kill_ring.truncate(KILL_RING_SIZE);
+    }
+
+    fn word_start(&self) -> usize {
+        let mut pos = self.pos;
+        while pos > 0 && !self.buf[pos-1].is_alphanumeric() {
+            pos -= 1;
+        }
+        while pos > 0 && self.buf[pos-1].is_alphanumeric() {
+            pos -= 1;
+        }
+        pos
+    }
+
+    fn word_end(&self) -> usize {
+        let mut pos = self.pos;
+        while pos < self.buf.len() && !self.buf[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        while pos < self.buf.len() && self.buf[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn blank_start(&self) -> usize {
+        let mut pos = self.pos;
+        while pos > 0 && self.buf[pos-1].is_whitespace() {
+            pos -= 1;
+        }
+        while pos > 0 && !self.buf[pos-1].is_whitespace() {
+            pos -= 1;
+        }
+        pos
+    }
+
+    fn transpose(&mut self) {
+        if self.pos == 0 || self.buf.len() < 2 {
+            return;
+        }
+        if self.pos == self.buf.len() {
+            self.pos -= 1;
+        }
+        self.buf.swap(self.pos-1, self.pos);
+        self.pos += 1;
+    }
+
+    fn draw(&self, prompt: &str, rprompt: &str, text: &[(char, Style)], pos: usize, footer: &[String])
+        -> io::Result<(usize, usize)>
+    {
+        let cols = columns();
+        let mut out = String::new();
+        if self.row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.row));
+        }
+        out.push_str("\r\x1b[J");
+        let (mut row, mut col) = (0, 0);
+        let mut visible = true;
+        for c in prompt.chars() {
+            match c {
+                '\x01' => visible = false,
+                '\x02' => visible = true,
+                '\n' => {
+                    out.push_str("\r\n");
+                    row += 1;
+                    col = 0;
+                },
+                _ if !visible => out.push(c),
+                _ => {
+                    advance(&mut row, &mut col, width(c), cols);
+                    out.push(c);
+                },
+            }
+        }
+        let mut cursor = None;
+        let mut current = Style::Plain;
+        for (i, &(c, style)) in text.iter().enumerate() {
+            if i == pos {
+                cursor = Some((row, col));
+            }
+            advance(&mut row, &mut col, width(c), cols);
+            push_cell(&mut out, c, style, &mut current);
+        }
+        if current != Style::Plain {
+            out.push_str("\x1b[0m");
+        }
+        if col == cols {
+            out.push_str("\r\n");
+            row += 1;
+            col = 0;
+        }
+        let prompt_rows = prompt.matches('\n').count();
+        let rwidth = visible_width(rprompt);
+        if rwidth > 0 && row == prompt_rows && col + 1 + rwidth < cols {
+            out.push_str(&format!("\x1b[{}G", cols - rwidth + 1));
+            out.push_str(&rprompt.replace(['\x01', '\x02'], ""));
+        }
+        let (cursor_row, cursor_col) = match cursor {
+            Some((row, col)) if col == cols => (row + 1, 0),
+            Some(cursor) => cursor,
+            None => (row, col),
+        };
+        for line in footer {
+            out.push_str("\r\n");
+            out.push_str(line);
+            row += 1;
+        }
+        if row > cursor_row {
+            out.push_str(&format!("\x1b[{}A", row - cursor_row));
+        }
+        out.push('\r');
+        if cursor_col > 0 {
+            out.push_str(&format!("\x1b[{}C", cursor_col));
+        }
+        self.write(&out)?;
+        Ok((cursor_row, row))
+    }
+
+    fn redraw(&self, prompt: &str, old: &[(char, Style)], cols: usize) -> io::Result<(usize, usize)> {
+        let text = self.cells(true);
+        let mut start = old.iter().zip(&text).take_while(|&(a, b)| a == b).count();
+        while start > 0 && (text.get(start).or(old.get(start)).is_some_and(|&(c, _)| width(c) == 0)) {
+            start -= 1;
+        }
+        let changed = old != &text[..];
+        let (prompt_row, prompt_col) = prompt_end(prompt, cols);
+        let (mut row, mut col) = (prompt_row, prompt_col);
+        let (mut cursor, mut damage) = (None, None);
+        let mut tail = String::new();
+        let mut current = Style::Plain;
+        for (i, &(c, style)) in text.iter().enumerate() {
+            if i == self.pos {
+                cursor = Some((row, col));
+            }
+            if i == start {
+                damage = Some((row, col));
+            }
+            advance(&mut row, &mut col, width(c), cols);
+            if i >= start {
+                push_cell(&mut tail, c, style, &mut current);
+            }
+        }
+        if current != Style::Plain {
+            tail.push_str("\x1b[0m");
+        }
+        let (damage_row, damage_col) = match damage.unwrap_or((row, col)) {
+            (row, col) if col == cols => (row + 1, 0),
+            damage => damage,
+        };
+        if col == cols {
+            if !tail.is_empty() {
+                tail.push_str("\r\n");
+            }
+            row += 1;
+            col = 0;
+        }
+        let (mut old_row, mut old_col) = (prompt_row, prompt_col);
+        for &(c, _) in old {
+            advance(&mut old_row, &mut old_col, width(c), cols);
+        }
+        let rwidth = visible_width(&self.rprompt);
+        let shown = |row: usize, col: usize| rwidth > 0 && row == prompt.matches('\n').count() && col + 1 + rwidth < cols;
+        let mut out = String::new();
+        let mut at = self.row;
+        if changed {
+            move_rows(&mut out, self.row, damage_row);
+            out.push('\r');
+            if damage_col > 0 {
+                out.push_str(&format!("\x1b[{}C", damage_col));
+            }
+            out.push_str("\x1b[J");
+            out.push_str(&tail);
+            if shown(row, col) && (damage_row == row || !shown(old_row, old_col)) {
+                out.push_str(&format!("\x1b[{}G", cols - rwidth + 1));
+                out.push_str(&self.rprompt.replace(['\x01', '\x02'], ""));
+            }
+            at = row;
+        }
+        let (cursor_row, cursor_col) = match cursor {
+            Some((row, col)) if col == cols => (row + 1, 0),
+            Some(cursor) => cursor,
+            None => (row, col),
+        };
+        move_rows(&mut out, at, cursor_row);
+        out.push('\r');
+        if cursor_col > 0 {
+            out.push_str(&format!("\x1b[{}C", cursor_col));
+        }
+        self.write(&out)?;
+        Ok((cursor_row, row))
+    }
+
+    fn refresh(&mut self, prompt: &str) -> io::Result<()> {
+        let cols = columns();
+        let (row, rows) = match self.drawn.take() {
+            Some(ref drawn) if drawn.prompt == prompt && drawn.rprompt == self.rprompt && drawn.cols == cols => {
+                self.redraw(prompt, &drawn.text, cols)?
+            },
+            _ => self.draw(prompt, &self.rprompt, &self.cells(true), self.pos, &[])?,
+        };
+        self.row = row;
+        self.rows = rows;
+        self.drawn = Some(Drawn {
+            prompt: prompt.to_owned(),
+            rprompt: self.rprompt.clone(),
+            text: self.cells(true),
+            cols,
+        });
+        Ok(())
+    }
+
+    fn finish(&mut self, s: &str) -> io::Result<()> {
+        let mut out = String::new();
+        if self.rows > self.row {
+            out.push_str(&format!("\x1b[{}B", self.rows - self.row));
+        }
+        out.push_str(s);
+        out.push_str("\r\n");
+        self.row = 0;
+        self.rows = 0;
+        self.drawn = None;
+        self.write(&out)
+    }
+
+    fn write(&self, s: &str) -> io::Result<()> {
+        let mut stdout = stdout();
+        stdout.write_all(s.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn history_prev(&mut self, history: &[&str]) {
+        if self.hisno > 0 {
+            if self.hisno == history.len() {
+                self.current = self.line();
+            }
+            self.hisno -= 1;
+            self.set_line(history[self.hisno]);
+        }
+    }
+
+    fn history_next(&mut self, history: &[&str]) {
+        if self.hisno < history.len() {
+            self.hisno += 1;
+            if self.hisno == history.len() {
+                let current = self.current.clone();
+                self.set_line(&current);
+            } else {
+                self.set_line(history[self.hisno]);
+            }
+        }
+    }
+
+    fn history_goto(&mut self, history: &[&str], hisno: usize) {
+        if self.hisno == history.len() {
+            self.current = self.line();
+        }
+        self.hisno = hisno;
+        self.set_line(history[hisno]);
+    }
+
+    fn search<C: Complete>(&mut self, history: &[&str], completer: &C) -> io::Result<Option<Key>> {
+        let orig = (self.line(), self.pos, self.hisno);
+        let mut query = String::new();
+        let mut found = None;
+        let mut older = None;
+        let mut start = self.hisno;
+        let mut older_start = usize::MAX;
+        loop {
+            if !query.is_empty() {
+                found = history[.. start].iter().rposition(|line| line.contains(&query));
+                older = None;
+                if let Some(index) = found {
+                    self.history_goto(history, index);
+                } else if let Some((index, line)) = completer.search_history(&query, older_start) {
+                    self.set_line(&line);
+                    older = Some(index);
+                }
+            }
+            let status = if found.is_none() && older.is_none() && !query.is_empty() { "failed " } else { "" };
+            let prompt = format!("({}reverse-i-search)`{}': ", status, query);
+            self.restyle(completer);
+            let (row, rows) = self.draw(&prompt, "", &self.cells(false), self.pos, &[])?;
+            self.row = row;
+            self.rows = rows;
+            self.drawn = None;
+            let key = match read_key()? {
+                Some(key) => key,
+                None => return Ok(Some(Key::Enter)),
+            };
+            match key {
+                Key::Char(c) => {
+                    query.push(c);
+                    start = found.map_or(start, |index| index + 1);
+                    older_start = older.map_or(older_start, |index| index + 1);
+                },
+                Key::Backspace | Key::Ctrl('h') => {
+                    query.pop();
+                    start = history.len();
+                    older_start = usize::MAX;
+                },
+                Key::Ctrl('r') => {
+                    if let Some(index) = found {
+                        start = index;
+                    } else if let Some(index) = older {
+                        older_start = index;
+                    }
+                },
+                Key::Ctrl('g') | Key::Ctrl('c') => {
+                    self.set_line(&orig.0);
+                    self.pos = orig.1;
+                    self.hisno = orig.2;
+                    return Ok(None);
+                },
+                Key::Esc => return Ok(None),
+                key => return Ok(Some(key)),
+            }
+        }
+    }
+
+    fn read_char_arg(&self) -> io::Result<Option<char>> {
+        match read_key()? {
+            Some(Key::Char(c)) => Ok(Some(c)),
+            _ => Ok(None),
+        }
+    }
+
+    fn vi_find(&self, cmd: char, target: char, count: usize) -> Option<usize> {
+        let mut pos = self.pos;
+        for _ in 0 .. count {
+            pos = match cmd {
+                'f' | 't' => {
+                    let skip = if cmd == 't' { 2 } else { 1 };
+                    let from = (pos + skip).min(self.buf.len());
+                    from + self.buf[from ..].iter().position(|&c| c == target)?
+                },
+                _ => {
+                    let skip = if cmd == 'T' { 1 } else { 0 };
+                    self.buf[.. pos.saturating_sub(skip)].iter().rposition(|&c| c == target)?
+                },
+            };
+        }
+        Some(match cmd {
+            't' => pos - 1,
+            'T' => pos + 1,
+            _ => pos,
+        })
+    }
+
+    fn vi_motion(&self, key: &Key, count: usize) -> io::Result<Option<(usize, bool)>> {
+        let len = self.buf.len();
+        let mut pos = self.pos;
+        let c = match *key {
+            Key::Char(c) => c,
+            Key::Left => 'h',
+            Key::Right => 'l',
+            Key::Home => '0',
+            Key::End => '$',
+            _ => return Ok(None),
+        };
+        let inclusive = match c {
+            'h' => {
+                pos = pos.saturating_sub(count);
+                false
+            },
+            'l' | ' ' => {
+                pos = (pos + count).min(len);
+                false
+            },
+            '0' => {
+                pos = 0;
+                false
+            },
+            '^' => {
+                pos = self.buf.iter().position(|c| !c.is_whitespace()).unwrap_or(len);
+                false
+            },
+            '$' => {
+                pos = len;
+                false
+            },
+            'w' | 'W' | 'b' | 'B' => {
+                for _ in 0 .. count {
+                    pos = match c {
+                        'w' | 'W' => vi_next_word(&self.buf, pos, c == 'W'),
+                        _ => vi_prev_word(&self.buf, pos, c == 'B'),
+                    };
+                }
+                false
+            },
+            'e' | 'E' => {
+                for _ in 0 .. count {
+                    pos = vi_word_end(&self.buf, pos, c == 'E');
+                }
+                true
+            },
+            'f' | 'F' | 't' | 'T' => {
+                let target = match self.read_char_arg()? {
+                    Some(target) => target,
+                    None => return Ok(None),
+                };
+                match self.vi_find(c, target, count) {
+                    Some(found) => pos = found,
+                    None => return Ok(None),
+                }
+                c == 'f' || c == 't'
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some((pos, inclusive)))
+    }
+
+    fn vi_text_object(&self, around: bool, big: bool) -> (usize, usize) {
+        let len = self.buf.len();
+        if len == 0 {
+            return (0, 0);
+        }
+        let pos = self.pos.min(len - 1);
+        let class = vi_class(self.buf[pos], big);
+        let mut start = pos;
+        while start > 0 && vi_class(self.buf[start-1], big) == class {
+            start -= 1;
+        }
+        let mut end = pos + 1;
+        while end < len && vi_class(self.buf[end], big) == class {
+            end += 1;
+        }
+        if around {
+            if end < len && vi_class(self.buf[end], big) == 0 {
+                while end < len && vi_class(self.buf[end], big) == 0 {
+                    end += 1;
+                }
+            } else {
+                while start > 0 && vi_class(self.buf[start-1], big) == 0 {
+                    start -= 1;
+                }
+            }
+        }
+        (start, end)
+    }
+
+    fn vi_operator(&mut self, op: char, count: usize) -> io::Result<()> {
+        let mut count = count;
+        let mut key = read_key()?;
+        while let Some(Key::Char(c @ '1' ..= '9')) = key {
+            let mut n = c.to_digit(10).unwrap() as usize;
+            loop {
+                key = read_key()?;
+                match key {
+                    Some(Key::Char(c)) if c.is_ascii_digit() => n = n * 10 + c.to_digit(10).unwrap() as usize,
+                    _ => break,
+                }
+            }
+            count *= n;
+        }
+        let key = match key {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let (start, end) = match key {
+            Key::Char(c) if c == op => (0, self.buf.len()),
+            Key::Char(c @ 'i') | Key::Char(c @ 'a') => match self.read_char_arg()? {
+                Some(obj @ 'w') | Some(obj @ 'W') => self.vi_text_object(c == 'a', obj == 'W'),
+                _ => return Ok(()),
+            },
+            Key::Char(c @ 'w') | Key::Char(c @ 'W') if op == 'c' &&
+                self.pos < self.buf.len() && !self.buf[self.pos].is_whitespace() => {
+                let mut end = self.pos;
+                for _ in 0 .. count {
+                    end = vi_word_end(&self.buf, end, c == 'W');
+                }
+                (self.pos, (end + 1).min(self.buf.len()))
+            },
+            key => match self.vi_motion(&key, count)? {
+                Some((target, _)) if target < self.pos => (target, self.pos),
+                Some((target, inclusive)) => (self.pos, (target + inclusive as usize).min(self.buf.len())),
+                None => return Ok(()),
+            },
+        };
+        self.register = self.buf[start .. end].iter().collect();
+        if op == 'y' {
+            self.pos = start;
+        } else {
+            self.kill(start, end);
+        }
+        if op == 'c' {
+            self.insert = true;
+        }
+        Ok(())
+    }
+
+    fn vi_search(&mut self, history: &[&str]) -> io::Result<()> {
+        let mut query = String::new();
+        loop {
+            let text: Vec<_> = query.chars().collect();
+            let cells: Vec<_> = text.iter().map(|&c| (c, Style::Plain)).collect();
+            let (row, rows) = self.draw("/", "", &cells, text.len(), &[])?;
+            self.row = row;
+            self.rows = rows;
+            self.drawn = None;
+            match read_key()? {
+                Some(Key::Char(c)) => query.push(c),
+                Some(Key::Backspace) if !query.is_empty() => {
+                    query.pop();
+                },
+                Some(Key::Enter) => break,
+                _ => return Ok(()),
+            }
+        }
+        if !query.is_empty() {
+            self.query = query;
+        }
+        self.vi_search_next(history, true);
+        Ok(())
+    }
+
+    fn vi_search_next(&mut self, history: &[&str], older: bool) {
+        if self.query.is_empty() {
+            return;
+        }
+        let found = if older {
+            history[.. self.hisno].iter().rposition(|line| line.contains(&self.query))
+        } else {
+            let from = (self.hisno + 1).min(history.len());
+            history[from ..].iter().position(|line| line.contains(&self.query)).map(|i| from + i)
+        };
+        if let Some(index) = found {
+            self.history_goto(history, index);
+            self.pos = 0;
+        }
+    }
+
+    fn vi_normal(&mut self, key: Key, history: &[&str]) -> io::Result<Option<Key>> {
+        let mut key = key;
+        let mut count = 0;
+        while let Key::Char(c) = key {
+            match c.to_digit(10) {
+                Some(n) if n > 0 || count > 0 => count = count * 10 + n as usize,
+                _ => break,
+            }
+            key = match read_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+        }
+        let count = count.max(1);
+        let len = self.buf.len();
+        let c = match key {
+            Key::Char(c) => c,
+            Key::Up => 'k',
+            Key::Down => 'j',
+            key @ Key::Enter | key @ Key::Ctrl(_) | key @ Key::Paste(_) => return Ok(Some(key)),
+            key => {
+                if let Some((pos, _)) = self.vi_motion(&key, count)? {
+                    self.pos = pos;
+                }
+                return Ok(None);
+            },
+        };
+        match c {
+            'i' => self.insert = true,
+            'a' => {
+                self.pos = (self.pos + 1).min(len);
+                self.insert = true;
+            },
+            'I' => {
+                self.pos = 0;
+                self.insert = true;
+            },
+            'A' => {
+                self.pos = len;
+                self.insert = true;
+            },
+            'x' | 's' => {
+                let end = (self.pos + count).min(len);
+                let start = self.pos;
+                self.register = self.kill(start, end);
+                self.insert = c == 's';
+            },
+            'X' => {
+                let start = self.pos.saturating_sub(count);
+                let end = self.pos;
+                self.register = self.kill(start, end);
+            },
+            'D' | 'C' => {
+                let start = self.pos;
+                self.register = self.kill(start, len);
+                self.insert = c == 'C';
+            },
+            'S' => {
+                self.register = self.kill(0, len);
+                self.insert = true;
+            },
+            'd' | 'c' | 'y' => self.vi_operator(c, count)?,
+            'p' | 'P' => {
+                if c == 'p' && self.pos < len {
+                    self.pos += 1;
+                }
+                let text = self.register.repeat(count);
+                self.insert(&text);
+                self.pos = self.pos.saturating_sub(1);
+            },
+            'r' => {
+                if let Some(r) = self.read_char_arg()? {
+                    if self.pos + count <= len {
+                        for i in 0 .. count {
+                            self.buf[self.pos + i] = r;
+                        }
+                        self.pos += count - 1;
+                    }
+                }
+            },
+            '~' => {
+                for _ in 0 .. count {
+                    if self.pos < self.buf.len() {
+                        let c = self.buf[self.pos];
+                        self.buf[self.pos] = if c.is_uppercase() {
+                            c.to_lowercase().next().unwrap_or(c)
+                        } else {
+                            c.to_uppercase().next().unwrap_or(c)
+                        };
+                        self.pos += 1;
+                    }
+                }
+            },
+            'k' | '-' => {
+                for _ in 0 .. count {
+                    self.history_prev(history);
+                }
+                self.pos = 0;
+            },
+            'j' | '+' => {
+                for _ in 0 .. count {
+                    self.history_next(history);
+                }
+                self.pos = 0;
+            },
+            '/' => self.vi_search(history)?,
+            'u' => return Ok(Some(Key::Ctrl('_'))),
+            'v' => return Ok(Some(Key::CtrlX('e'))),
+            'n' => self.vi_search_next(history, true),
+            'N' => self.vi_search_next(history, false),
+            _ => {
+                if let Some((pos, _)) = self.vi_motion(&key, count)? {
+                    self.pos = pos;
+                }
+            },
+        }
+        Ok(None)
+    }
+
+    pub fn set_vi(&mut self, vi: bool) {
+        self.vi = vi;
+    }
+
+    pub fn set_autosuggest(&mut self, autosuggest: bool) {
+        self.autosuggest = autosuggest;
+    }
+
+    pub fn set_keymap(&mut self, keymap: &Keymap) {
+        self.keymap = keymap.clone();
+    }
+
+    pub fn set_highlight(&mut self, highlight: bool) {
+        self.highlight = highlight;
+    }
+
+    fn restyle<C: Complete>(&mut self, completer: &C) {
+        if self.highlight && self.styled != self.buf {
+            self.styles = completer.highlight(&self.line());
+            self.styled = self.buf.clone();
+        }
+    }
+
+    fn cells(&self, ghost: bool) -> Vec<(char, Style)> {
+        let styles = if self.highlight && self.styled == self.buf { &self.styles[..] } else { &[] };
+        let mut cells: Vec<_> = self.buf.iter().enumerate()
+            .map(|(i, &c)| (c, styles.get(i).cloned().unwrap_or(Style::Plain)))
+            .collect();
+        if ghost {
+            cells.extend(self.suggestion.iter().map(|&c| (c, Style::Ghost)));
+        }
+        cells
+    }
+
+    fn suggest(&mut self, history: &[&str]) {
+        self.suggestion.clear();
+        if !self.autosuggest || self.buf.is_empty() || self.pos != self.buf.len() || self.vi && !self.insert {
+            return;
+        }
+        let line = self.line();
+        if let Some(entry) = history.iter().rev().find(|entry| entry.len() > line.len() && entry.starts_with(&line)) {
+            self.suggestion = entry[line.len() ..].chars().collect();
+        }
+    }
+
+    fn accept_suggestion(&mut self, word: bool) {
+        let len = if word {
+            let blank = self.suggestion.iter().take_while(|c| c.is_whitespace()).count();
+            blank + self.suggestion[blank ..].iter().take_while(|c| !c.is_whitespace()).count()
+        } else {
+            self.suggestion.len()
+        };
+        let text: String = self.suggestion.drain(.. len).collect();
+        self.insert(&text);
+    }
+
+    pub fn set_rprompt(&mut self, rprompt: String) {
+        self.rprompt = rprompt;
+    }
+
+    pub fn read_line<C: Complete>(&mut self, prompt: &str, history: &[&str], completer: &C) -> io::Result<Option<String>> {
+        let mut prompt = prompt.to_owned();
+        let mut _raw = RawMode::enable()?;
+        self.buf.clear();
+        self.suggestion.clear();
+        self.pos = 0;
+        self.hisno = history.len();
+        self.current.clear();
+        self.insert = true;
+        self.undo.clear();
+        self.row = 0;
+        self.rows = 0;
+        self.drawn = None;
+        let mut pending = None;
+        let mut last_arg = None;
+        let mut last_kill = false;
+        let mut last_yank = None;
+        let mut last_typed = false;
+        let mut last_tab = false;
+        let mut line_match = None;
+        self.restyle(completer);
+        self.refresh(&prompt)?;
+        loop {
+            while pending.is_none() && completer.prompt_pending() && !self::pending(PROMPT_POLL) {
+                if let Some((new_prompt, rprompt)) = completer.poll_prompt() {
+                    prompt = new_prompt;
+                    self.rprompt = rprompt;
+                    self.refresh(&prompt)?;
+                }
+            }
+            let key = match pending.take() {
+                Some(key) => key,
+                None => match read_key()? {
+                    Some(key) => key,
+                    None if self.buf.is_empty() => return Ok(None),
+                    None => Key::Enter,
+                },
+            };
+            let key = match key {
+                Key::Ctrl('x') => match read_key()? {
+                    Some(Key::Ctrl(c)) => Key::CtrlX(c),
+                    _ => Key::Unknown,
+                },
+                key => key,
+            };
+            let before = (self.buf.clone(), self.pos);
+            let key = if self.vi && !self.insert {
+                match self.vi_normal(key, history)? {
+                    Some(key) => key,
+                    None => {
+                        if !self.insert && self.pos > 0 && self.pos == self.buf.len() {
+                            self.pos -= 1;
+                        }
+                        self.record(before, false);
+                        last_typed = false;
+                        self.restyle(completer);
+                        self.refresh(&prompt)?;
+                        continue;
+                    },
+                }
+            } else {
+                key
+            };
+            let action = self.keymap.get(&key);
+            let yank_arg = action == Some(Action::YankLastArg);
+            let killing = matches!(action, Some(Action::UnixWordRubout | Action::UnixLineDiscard | Action::KillLine |
+                                                Action::KillWord | Action::KillWholeLine));
+            let yanking = matches!(action, Some(Action::Yank | Action::YankPop));
+            let typed = matches!(key, Key::Char(_)) && action == Some(Action::SelfInsert);
+            if matches!(action, Some(Action::AcceptLine | Action::SendBreak)) && !self.suggestion.is_empty() {
+                self.suggestion.clear();
+                self.refresh(&prompt)?;
+            }
+            match action {
+                _ if self.vi && key == Key::Esc => {
+                    self.insert = false;
+                    if self.pos > 0 {
+                        self.pos -= 1;
+                    }
+                },
+                Some(Action::AcceptLine) => {
+                    self.finish("")?;
+                    return Ok(Some(self.line()));
+                },
+                Some(Action::DeleteChar) if self.buf.is_empty() && key == Key::Ctrl('d') => {
+                    self.finish("")?;
+                    return Ok(None);
+                },
+                Some(Action::SendBreak) => {
+                    self.finish("^C")?;
+                    self.buf.clear();
+                    self.pos = 0;
+                    self.hisno = history.len();
+                    self.insert = true;
+                },
+                Some(Action::SelfInsert) => {
+                    if let Key::Char(c) = key {
+                        self.buf.insert(self.pos, c);
+                        self.pos += 1;
+                    }
+                },
+                Some(Action::BackwardDeleteChar) => {
+                    if self.pos > 0 {
+                        self.pos -= 1;
+                        self.buf.remove(self.pos);
+                    }
+                },
+                Some(Action::DeleteChar) => {
+                    if self.pos < self.buf.len() {
+                        self.buf.remove(self.pos);
+                    }
+                },
+                Some(Action::BackwardChar) => {
+                    if self.pos > 0 {
+                        self.pos -= 1;
+                    }
+                },
+                Some(Action::ForwardChar | Action::EndOfLine) if !self.suggestion.is_empty() => {
+                    self.accept_suggestion(false);
+                },
+                Some(Action::ForwardWord) if !self.suggestion.is_empty() => self.accept_suggestion(true),
+                Some(Action::ForwardChar) => {
+                    if self.pos < self.buf.len() {
+                        self.pos += 1;
+                    }
+                },
+                Some(Action::BackwardWord) => self.pos = self.word_start(),
+                Some(Action::ForwardWord) => self.pos = self.word_end(),
+                Some(Action::UnixWordRubout) => {
+                    let (start, end) = (self.blank_start(), self.pos);
+                    self.kill_to_ring(start, end, last_kill);
+                },
+                Some(Action::UnixLineDiscard) => {
+                    let end = self.pos;
+                    self.kill_to_ring(0, end, last_kill);
+                },
+                Some(Action::KillLine) => {
+                    let (start, end) = (self.pos, self.buf.len());
+                    self.kill_to_ring(start, end, last_kill);
+                },
+                Some(Action::KillWholeLine) => {
+                    let end = self.buf.len();
+                    self.kill_to_ring(0, end, last_kill);
+                },
+                Some(Action::ClearLine) => {
+                    self.buf.clear();
+                    self.pos = 0;
+                },
+                Some(Action::KillWord) => {
+                    let (start, end) = (self.pos, self.word_end());
+                    self.kill_to_ring(start, end, last_kill);
+                },
+                Some(Action::Yank) => {
+                    if let Some(text) = self.kill_ring.front().cloned() {
+                        let start = self.pos;
+                        self.insert(&text);
+                        last_yank = Some((start, 0));
+                    }
+                },
+                Some(Action::YankPop) => {
+                    if let Some((start, index)) = last_yank {
+                        let end = self.pos;
+                        self.kill(start, end);
+                        let index = (index + 1) % self.kill_ring.len();
+                        let text = self.kill_ring[index].clone();
+                        self.insert(&text);
+                        last_yank = Some((start, index));
+                    }
+                },
+                Some(Action::Complete) => {
+                    let (start, candidates) = completer.complete(&self.buf, self.pos);
+                    pending = self.complete(&prompt, start, &candidates, last_tab)?;
+                },
+                Some(Action::TransposeChars) => self.transpose(),
+                Some(Action::Undo) => self.undo(),
+                Some(Action::EditCommandLine) => match completer.external_editor() {
+                    Some(editor) => {
+                        self.finish("")?;
+                        drop(_raw);
+                        if let Err(error) = self.edit_external(&editor) {
+                            eprintln!("I/O Error: {}", error);
+                        }
+                        _raw = RawMode::enable()?;
+                        self.insert = true;
+                    },
+                    None => self.write("\x07")?,
+                },
+                Some(Action::YankLastArg) => {
+                    let (back, len) = match last_arg {
+                        Some((back, len)) => (back + 1, len),
+                        None => (1, 0),
+                    };
+                    let arg = history.len().checked_sub(back)
+                        .and_then(|index| history[index].split_whitespace().last());
+                    if let Some(arg) = arg {
+                        let start = self.pos - len;
+                        self.kill(start, self.pos);
+                        self.insert(arg);
+                        last_arg = Some((back, arg.chars().count()));
+                    }
+                },
+                Some(Action::HistorySearchBackward) => {
+                    let (prefix, end) = line_match.take()
+                        .unwrap_or_else(|| (self.buf[.. self.pos].iter().collect::<String>(), history.len()));
+                    let line = self.line();
+                    let found = history[.. end].iter()
+                        .rposition(|entry| entry.starts_with(&prefix) && *entry != line);
+                    match found {
+                        Some(index) => {
+                            self.set_line(history[index]);
+                            line_match = Some((prefix, index));
+                        },
+                        None => {
+                            self.write("\x07")?;
+                            line_match = Some((prefix, end));
+                        },
+                    }
+                },
+                Some(Action::ClearScreen) => {
+                    self.write("\x1b[H\x1b[2J")?;
+                    self.row = 0;
+                    self.drawn = None;
+                },
+                Some(Action::ReverseSearchHistory) => {
+                    if self.hisno == history.len() {
+                        self.current = self.line();
+                    }
+                    pending = self.search(history, completer)?;
+                },
+                Some(Action::BeginningOfLine) => self.pos = 0,
+                Some(Action::EndOfLine) => self.pos = self.buf.len(),
+                Some(Action::PreviousHistory) => self.history_prev(history),
+                Some(Action::NextHistory) => self.history_next(history),
+                None => {
+                    if let Key::Paste(ref text) = key {
+                        self.insert(text);
+                    }
+                },
+            }
+            if !yank_arg {
+                last_arg = None;
+            }
+            if !yanking {
+                last_yank = None;
+            }
+            last_kill = killing;
+            if action != Some(Action::Undo) {
+                self.record(before, typed && last_typed);
+            }
+            last_typed = typed;
+            last_tab = action == Some(Action::Complete);
+            if action != Some(Action::HistorySearchBackward) {
+                line_match = None;
+            }
+            self.suggest(history);
+            self.restyle(completer);
+            self.refresh(&prompt)?;
+        }
+    }
+}
+
+fn vi_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn vi_next_word(buf: &[char], pos: usize, big: bool) -> usize {
+    let mut pos = pos;
+    if pos < buf.len() {
+        let class = vi_class(buf[pos], big);
+        while pos < buf.len() && class != 0 && vi_class(buf[pos], big) == class {
+            pos += 1;
+        }
+    }
+    while pos < buf.len() && vi_class(buf[pos], big) == 0 {
+        pos += 1;
+    }
+    pos
+}
+
+fn vi_prev_word(buf: &[char], pos: usize, big: bool) -> usize {
+    if pos == 0 {
+        return 0;
+    }
+    let mut pos = pos - 1;
+    while pos > 0 && vi_class(buf[pos], big) == 0 {
+        pos -= 1;
+    }
+    let class = vi_class(buf[pos], big);
+    while pos > 0 && vi_class(buf[pos-1], big) == class {
+        pos -= 1;
+    }
+    pos
+}
+
+fn vi_word_end(buf: &[char], pos: usize, big: bool) -> usize {
+    let mut pos = pos + 1;
+    while pos < buf.len() && vi_class(buf[pos], big) == 0 {
+        pos += 1;
+    }
+    if pos >= buf.len() {
+        return buf.len().saturating_sub(1);
+    }
+    let class = vi_class(buf[pos], big);
+    while pos + 1 < buf.len() && vi_class(buf[pos+1], big) == class {
+        pos += 1;
+    }
+    pos
+}
@@ -0,0 +1,39 @@
+use libc::c_int;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum ShellError {
+    Parse(String),
+    Expansion(String),
+    History(String),
+    Io(String, io::Error),
+    Spawn(&'static str, io::Error),
+    Restricted(String),
+}
+
+impl ShellError {
+    pub fn status(&self) -> c_int {
+        match *self {
+            ShellError::Parse(_) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        matches!(*self, ShellError::Parse(_) | ShellError::Expansion(_))
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShellError::Parse(ref message) => write!(f, "Parsing Error: {}", message),
+            ShellError::Expansion(ref message) => write!(f, "Expansion Error: {}", message),
+            ShellError::History(ref message) => write!(f, "History Error: {}", message),
+            ShellError::Io(ref path, ref error) => write!(f, "I/O Error: {}: {}", path, error),
+            ShellError::Spawn(call, ref error) => write!(f, "Spawn Error: {}: {}", call, error),
+            ShellError::Restricted(ref message) => write!(f, "Restricted: {}", message),
+        }
+    }
+}
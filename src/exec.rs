@@ -0,0 +1,484 @@
+use alias;
+use bash;
+use builtins::Io;
+use complete::Spec;
+use error::ShellError;
+use expand::expand;
+use jobs;
+use lexer::RESERVED;
+use libc::{self, c_int, pid_t};
+use parser::{parse, AndOr, Cmd, CmdLine, Op, Time};
+use pathindex::PathIndex;
+use shell::Rush;
+use std::fs::OpenOptions;
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::{Duration, Instant};
+use sys::{self, errno, perror, set_errno, Libc, Redirect, Sys};
+
+const RESTRICTED_VARS: &[&str] = &["PATH", "SHELL", "ENV", "HISTFILE", "VISUAL", "EDITOR", "FCEDIT", "TMPDIR"];
+const RESTRICTED_BUILTINS: &[&str] = &["cd", "pushd", "popd", "j", "rushenv"];
+
+impl Cmd<&str> {
+    fn expand(&self, rush: &Rush) -> Result<Cmd<String>, ShellError> {
+        if rush.options.posix && RESERVED.contains(&self.prog()) {
+            return Err(ShellError::Parse(format!("{}: reserved word is not supported", self.prog())));
+        }
+        let mut cmd = Vec::new();
+        for word in &self.cmd {
+            let word = expand(word, rush)?;
+            if !word.is_empty() {
+                cmd.push(word);
+            }
+        }
+        Ok(Cmd {
+            cmd,
+        })
+    }
+}
+
+impl Cmd<String> {
+    fn is_builtin(&self, rush: &Rush) -> bool {
+        self.is_assignment() || rush.builtins.contains_key(self.prog())
+    }
+
+    fn exec<S: Sys>(&self, rush: &mut Rush, sys: &mut S, io: &mut Io) -> c_int {
+        if self.is_assignment() {
+            for word in &self.cmd {
+                let pos = word.find('=').unwrap();
+                rush.vars.set(&word[.. pos], &word[pos+1 ..]);
+            }
+            return 0;
+        }
+        if let Some(builtin) = rush.builtins.get(self.prog()).cloned() {
+            let status = builtin.run(rush, &self.cmd, io);
+            return match io.finish() {
+                Some(error) => {
+                    if error.kind() != ErrorKind::BrokenPipe {
+                        eprintln!("{}: write error: {}", self.prog(), error);
+                    }
+                    if status == 0 { 1 } else { status }
+                },
+                None => status,
+            };
+        }
+        sys.execvp(&self.cmd);
+        let errno = errno();
+        if errno == libc::ENOENT && !self.prog().contains('/') {
+            rush.vars.set("RUSH_COMMAND_NAME", self.prog());
+            rush.vars.set("RUSH_COMMAND_ARGS", &self.cmd[1 ..].join(" "));
+            rush.run_hooks("on_command_not_found");
+            set_errno(errno);
+        }
+        perror(self.prog());
+        if errno == libc::ENOENT && !self.prog().contains('/') && rush.interactive {
+            // The prefetch thread may have held the index lock when we forked.
+            rush.path_index = PathIndex::new();
+            let suggestions = rush.suggest_commands(self.prog());
+            if !suggestions.is_empty() {
+                eprintln!("rush: did you mean: {}?", suggestions.join(", "));
+            }
+        }
+        if errno == libc::ENOENT { 127 } else { 126 }
+    }
+}
+
+fn close_pipes<S: Sys>(sys: &mut S, fd: &[[c_int; 2]], i: usize) {
+    for (j, fds) in fd.iter().enumerate() {
+        if j+1 >= i {
+            sys.close(fds[0]);
+        }
+        if j >= i {
+            sys.close(fds[1]);
+        }
+    }
+}
+
+impl CmdLine<String> {
+    fn dupin<S: Sys>(&self, sys: &mut S) {
+        if let Some(ref path) = self.filein {
+            let fdin = sys.openr(path);
+            if fdin == -1 {
+                perror(&("I/O Error: ".to_owned() + path));
+                sys._exit(1);
+            } else {
+                sys.dup2(fdin, 0);
+                if fdin != 0 {
+                    sys.close(fdin);
+                }
+            }
+        }
+    }
+
+    fn dupout<S: Sys>(&self, sys: &mut S) {
+        if let Some(ref path) = self.fileout {
+            let fdout = sys.openw(path);
+            if fdout == -1 {
+                perror(&("I/O Error: ".to_owned() + path));
+                sys._exit(1);
+            } else {
+                sys.dup2(fdout, 1);
+                if fdout != 1 {
+                    sys.close(fdout);
+                }
+            }
+        }
+    }
+
+    fn builtin_io(&self) -> Result<Io, ShellError> {
+        let path = match self.fileout {
+            Some(ref path) => path,
+            None => return Ok(Io::stdout()),
+        };
+        OpenOptions::new().write(true).create(true).truncate(true).mode(0o644).open(path)
+            .map(|file| Io::new(Box::new(file)))
+            .map_err(|error| ShellError::Io(path.clone(), error))
+    }
+
+    fn correct(&mut self, rush: &Rush) {
+        for cmd in &mut self.cmds {
+            if cmd.is_builtin(rush) || cmd.prog().is_empty() || cmd.prog().contains('/') {
+                continue;
+            }
+            if rush.path_commands().iter().any(|name| name == cmd.prog()) {
+                continue;
+            }
+            let suggestion = match rush.suggest_commands(cmd.prog()).into_iter().next() {
+                Some(suggestion) => suggestion,
+                None => continue,
+            };
+            eprint!("rush: correct '{}' to '{}' [yn]? ", cmd.prog(), suggestion);
+            let mut answer = String::new();
+            if io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y") {
+                cmd.cmd[0] = suggestion;
+            }
+        }
+    }
+
+    fn check_restricted(&self) -> Result<(), ShellError> {
+        let restricted = |message: String| Err(ShellError::Restricted(message));
+        if let Some(ref path) = self.fileout {
+            return restricted(format!("{}: cannot redirect output", path));
+        }
+        for cmd in &self.cmds {
+            let prog = cmd.prog();
+            let assigns = if cmd.is_assignment() { &cmd.cmd[..] } else if prog == "export" { &cmd.cmd[1 ..] } else { &[] };
+            if !cmd.is_assignment() && prog.contains('/') {
+                return restricted(format!("{}: cannot specify `/' in command names", prog));
+            }
+            if RESTRICTED_BUILTINS.contains(&prog) {
+                return restricted(format!("{}: restricted", prog));
+            }
+            for word in assigns {
+                let name = word.split('=').next().unwrap_or("");
+                if RESTRICTED_VARS.contains(&name) {
+                    return restricted(format!("{}: readonly variable", name));
+                }
+            }
+            if prog == "fc" {
+                let flags: String = cmd.cmd[1 ..].iter()
+                    .filter(|arg| arg.len() > 1 && arg.starts_with('-') && arg[1 ..].parse::<usize>().is_err())
+                    .map(|arg| &arg[1 ..])
+                    .collect();
+                if !flags.contains('l') && !flags.contains('s') {
+                    return restricted("fc: cannot run an editor".to_owned());
+                }
+            }
+            if prog == "complete" && cmd.cmd.get(1).is_some_and(|arg| arg == "--load") {
+                return restricted("complete: --load: restricted".to_owned());
+            }
+            if prog == "complete" || prog == "compgen" {
+                if let Ok((Spec { command: Some(command), .. }, _)) = Spec::parse(&cmd.cmd[1 ..]) {
+                    if command.contains('/') {
+                        return restricted(format!("{}: {}: cannot specify `/' in command names", prog, command));
+                    }
+                }
+            }
+            let files: Vec<_> = match prog {
+                "source" | "." => cmd.cmd[1 ..].iter().collect(),
+                "history" => cmd.cmd.iter().skip(2).collect(),
+                "bind" => cmd.cmd.windows(2).filter(|pair| pair[0] == "-f").map(|pair| &pair[1]).collect(),
+                _ => Vec::new(),
+            };
+            if let Some(file) = files.iter().find(|file| file.contains('/')) {
+                return restricted(format!("{}: {}: cannot specify `/' in file names", prog, file));
+            }
+        }
+        Ok(())
+    }
+
+    fn trace(&self, rush: &Rush) {
+        let ps4 = rush.vars.get("PS4").unwrap_or("+ ");
+        let ps4 = expand(ps4, rush).unwrap_or_else(|_| ps4.to_owned());
+        for cmd in &self.cmds {
+            eprintln!("{}{}", ps4, cmd.cmd.join(" "));
+        }
+    }
+
+    fn try_spawn<S: Sys>(&self, rush: &Rush, sys: &mut S, fd: &[[c_int; 2]], i: usize) -> Option<pid_t> {
+        if self.cmds[i].is_builtin(rush) {
+            return None;
+        }
+        let stdin = match self.filein {
+            Some(ref path) if i == 0 => Redirect::Read(path),
+            _ if i > 0 => Redirect::Fd(fd[i-1][0]),
+            _ => Redirect::Fd(0),
+        };
+        let stdout = match self.fileout {
+            Some(ref path) if i == fd.len() => Redirect::Write(path),
+            _ if i < fd.len() => Redirect::Fd(fd[i][1]),
+            _ => Redirect::Fd(1),
+        };
+        match sys.spawn(&self.cmds[i].cmd, stdin, stdout) {
+            -1 => None,
+            pid => Some(pid),
+        }
+    }
+
+    fn spawn<S: Sys>(&self, rush: &mut Rush, sys: &mut S) -> Result<Vec<pid_t>, ShellError> {
+        let mut pids = Vec::new();
+        let len = self.len();
+        if len == 1 && self.cmds[0].is_builtin(rush) {
+            let mut io = self.builtin_io()?;
+            rush.status = self.cmds[0].exec(rush, sys, &mut io);
+            return Ok(pids);
+        }
+        let mut fd = vec![[0; 2]; len.saturating_sub(1)];
+        for i in 0 .. fd.len() {
+            if sys.pipe(&mut fd[i]) == -1 {
+                let error = io::Error::last_os_error();
+                close_pipes(sys, &fd[.. i], 0);
+                return Err(ShellError::Spawn("pipe", error));
+            }
+        }
+        for i in 0 .. len {
+            let pid = match self.try_spawn(rush, sys, &fd, i) {
+                Some(pid) => pid,
+                None => sys.fork(),
+            };
+            if pid == -1 {
+                let error = io::Error::last_os_error();
+                close_pipes(sys, &fd, i);
+                jobs::wait(sys, pids);
+                return Err(ShellError::Spawn("fork", error));
+            }
+            pids.push(pid);
+            if pid == 0 {
+                if i == 0 {
+                    self.dupin(sys);
+                } else {
+                    sys.dup2(fd[i-1][0], 0);
+                }
+                if i == len-1 {
+                    self.dupout(sys);
+                } else {
+                    sys.dup2(fd[i][1], 1);
+                }
+                close_pipes(sys, &fd, i);
+                let status = self.cmds[i].exec(rush, sys, &mut Io::stdout());
+                sys._exit(status);
+            }
+            if i > 0 {
+                sys.close(fd[i-1][0]);
+            }
+            if i < len-1 {
+                sys.close(fd[i][1]);
+            }
+        }
+        Ok(pids)
+    }
+}
+
+impl CmdLine<&str> {
+    fn expand(&self, rush: &Rush) -> Result<CmdLine<String>, ShellError> {
+        let mut cmds = Vec::new();
+        for cmd in &self.cmds {
+            cmds.push(cmd.expand(rush)?);
+        }
+        let expand_path = |path: &Option<&str>| match *path {
+            Some(path) => expand(path, rush).map(Some),
+            None => Ok(None),
+        };
+        Ok(CmdLine {
+            time: self.time,
+            cmds,
+            filein: expand_path(&self.filein)?,
+            fileout: expand_path(&self.fileout)?,
+        })
+    }
+
+    pub fn exec<S: Sys>(&self, rush: &mut Rush, sys: &mut S) -> Result<Vec<pid_t>, ShellError> {
+        let mut cmdline = self.expand(rush)?;
+        if rush.restricted {
+            cmdline.check_restricted()?;
+        }
+        if rush.options.correct && rush.interactive {
+            cmdline.correct(rush);
+        }
+        if rush.options.xtrace {
+            cmdline.trace(rush);
+        }
+        cmdline.spawn(rush, sys)
+    }
+}
+
+impl Rush {
+    pub fn execute(&mut self, line: &str) {
+        if self.options.bashcompat {
+            for command in bash::split(line) {
+                self.execute_line(command);
+            }
+        } else {
+            self.execute_line(line);
+        }
+    }
+
+    fn execute_line(&mut self, line: &str) {
+        let expanded = alias::expand(line, &self.aliases);
+        let line = expanded.as_deref().unwrap_or(line);
+        let result = parse(line).and_then(|lists| {
+            lists.iter().try_for_each(|list| self.exec(list, &mut Libc))
+        });
+        if let Err(error) = result {
+            self.report(&error);
+        }
+    }
+
+    pub fn report(&mut self, error: &ShellError) {
+        if self.use_color(2) {
+            eprintln!("\x1b[31m{}\x1b[0m", error);
+        } else {
+            eprintln!("{}", error);
+        }
+        self.status = error.status();
+        if error.is_fatal() && !self.interactive {
+            self.exit(self.status);
+        }
+    }
+
+    pub fn exec<S: Sys>(&mut self, list: &AndOr, sys: &mut S) -> Result<(), ShellError> {
+        if !list.back {
+            return self.run_list(list, sys);
+        }
+        let pids = if list.rest.is_empty() {
+            list.first.exec(self, sys)?
+        } else {
+            let pid = sys.fork();
+            if pid == -1 {
+                return Err(ShellError::Spawn("fork", io::Error::last_os_error()));
+            }
+            if pid == 0 {
+                self.interactive = false;
+                self.login = false;
+                self.hooks.remove("on_exit");
+                let status = match self.run_list(list, sys) {
+                    Ok(()) => self.status,
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        error.status()
+                    },
+                };
+                sys._exit(status);
+            }
+            vec![pid]
+        };
+        self.jobs.push(pids, list.to_string());
+        self.status = 0;
+        Ok(())
+    }
+
+    fn run_list<S: Sys>(&mut self, list: &AndOr, sys: &mut S) -> Result<(), ShellError> {
+        self.run_pipeline(&list.first, sys)?;
+        let mut last = list.rest.is_empty();
+        for (i, &(op, ref cmdline)) in list.rest.iter().enumerate() {
+            if (op == Op::And) == (self.status == 0) {
+                self.run_pipeline(cmdline, sys)?;
+                last = i == list.rest.len()-1;
+            }
+        }
+        if last && self.options.errexit && self.status != 0 {
+            self.exit(self.status);
+        }
+        Ok(())
+    }
+
+    fn run_pipeline<S: Sys>(&mut self, cmdline: &CmdLine<&str>, sys: &mut S) -> Result<(), ShellError> {
+        let start = cmdline.time.map(|_| (Instant::now(), sys::cputime()));
+        let pids = cmdline.exec(self, sys)?;
+        if let Some(status) = jobs::wait(sys, pids) {
+            self.status = sys::exit_status(status);
+            self.sanitize_terminal(status);
+        }
+        if let (Some(time), Some((instant, (user, system)))) = (cmdline.time, start) {
+            let real = instant.elapsed();
+            let (user_end, system_end) = sys::cputime();
+            let times = [real, user_end - user, system_end - system];
+            let format = match time {
+                Time::Posix => "real %2R\nuser %2U\nsys %2S",
+                Time::Default => self.vars.get("TIMEFORMAT").unwrap_or("\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS"),
+            };
+            if !format.is_empty() {
+                eprintln!("{}", format_times(format, times));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_seconds(duration: Duration, precision: usize, long: bool) -> String {
+    let secs = duration.as_secs_f64();
+    if long {
+        let minutes = (secs / 60.0).floor();
+        format!("{}m{:.*}s", minutes, precision, secs - minutes * 60.0)
+    } else {
+        format!("{:.*}", precision, secs)
+    }
+}
+
+fn format_times(format: &str, [real, user, system]: [Duration; 3]) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(c) => {
+                    result.push('\\');
+                    result.push(c);
+                },
+                None => result.push('\\'),
+            },
+            '%' => {
+                let precision = chars.next_if(char::is_ascii_digit).map(|digit| digit.to_digit(10).unwrap().min(3) as usize);
+                let long = chars.next_if_eq(&'l').is_some();
+                let duration = match chars.next() {
+                    Some('R') => real,
+                    Some('U') => user,
+                    Some('S') => system,
+                    Some('P') => {
+                        let percent = if real.is_zero() { 0.0 } else { (user + system).as_secs_f64() * 100.0 / real.as_secs_f64() };
+                        result.push_str(&format!("{:.2}", percent));
+                        continue;
+                    },
+                    Some('%') => {
+                        result.push('%');
+                        continue;
+                    },
+                    Some(c) => {
+                        result.push('%');
+                        result.push(c);
+                        continue;
+                    },
+                    None => {
+                        result.push('%');
+                        continue;
+                    },
+                };
+                result.push_str(&format_seconds(duration, precision.unwrap_or(3), long));
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
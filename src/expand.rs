@@ -0,0 +1,75 @@
+use error::ShellError;
+use lexer::is_name;
+use shell::{Rush, OPTIONS};
+use std::process;
+
+fn special(name: &str, rush: &Rush) -> Option<String> {
+    match name {
+        "$" => Some(process::id().to_string()),
+        "0" => Some(rush.name.clone()),
+        "#" => Some("0".to_owned()),
+        "-" => Some(OPTIONS.iter()
+            .filter(|opt| rush.options.get(opt.0) == Some(true))
+            .filter_map(|opt| opt.1)
+            .collect()),
+        _ if name.len() == 1 && name.starts_with(|c: char| c.is_ascii_digit()) => Some(String::new()),
+        _ => None,
+    }
+}
+
+pub fn expand(word: &str, rush: &Rush) -> Result<String, ShellError> {
+    let mut result = String::new();
+    let mut quote = None;
+    let mut chars = word.chars();
+    if word.starts_with('~') {
+        let end = word.find('/').unwrap_or(word.len());
+        if let Some(dir) = rush.tilde_dir(&word[1 .. end]) {
+            result.push_str(&dir);
+            chars = word[end ..].chars();
+        }
+    }
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (c, Some('\'')) => result.push(c),
+            ('\\', None) => result.extend(chars.next()),
+            ('\\', Some(_)) if chars.as_str().starts_with(['$', '`', '"', '\\']) => result.extend(chars.next()),
+            ('$', _) => {
+                let rest = chars.as_str();
+                let (name, len) = if rest.starts_with('?') {
+                    ("?", 1)
+                } else if rest.starts_with(['$', '#', '-', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9']) {
+                    (&rest[.. 1], 1)
+                } else if rest.starts_with('{') {
+                    match rest.find('}') {
+                        Some(end) => (&rest[1 .. end], end+1),
+                        None => ("", 0),
+                    }
+                } else {
+                    let end = rest.find(|c: char| c != '_' && !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+                    (&rest[.. end], end)
+                };
+                if name == "?" {
+                    result.push_str(&rush.status.to_string());
+                } else if let Some(value) = special(name, rush) {
+                    result.push_str(&value);
+                } else if is_name(name) {
+                    match rush.vars.get(name) {
+                        Some(value) => result.push_str(value),
+                        None if rush.options.nounset => return Err(ShellError::Expansion(format!("{}: unbound variable", name))),
+                        None => {},
+                    }
+                } else if rush.options.posix && rest.starts_with('{') {
+                    return Err(ShellError::Expansion(format!("${{{}}}: bad substitution", name)));
+                } else {
+                    result.push('$');
+                    continue;
+                }
+                chars = rest[len ..].chars();
+            },
+            (c, _) => result.push(c),
+        }
+    }
+    Ok(result)
+}
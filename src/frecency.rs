@@ -0,0 +1,111 @@
+use history::{open_locked, replace_private};
+use libc;
+use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+
+const MAX_RANK: f64 = 9000.0;
+
+pub struct Entry {
+    pub path: String,
+    pub rank: f64,
+    pub time: i64,
+}
+
+impl Entry {
+    pub fn score(&self, now: i64) -> f64 {
+        let age = now - self.time;
+        if age < 3600 {
+            self.rank * 4.0
+        } else if age < 86400 {
+            self.rank * 2.0
+        } else if age < 604800 {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+
+    fn matches(&self, words: &[String]) -> bool {
+        let path = self.path.to_lowercase();
+        let mut rest = path.as_str();
+        for word in words {
+            let word = word.to_lowercase();
+            match rest.find(&word) {
+                Some(pos) => rest = &rest[pos + word.len() ..],
+                None => return false,
+            }
+        }
+        words.is_empty() || !rest.contains('/')
+    }
+}
+
+pub fn load(path: &str) -> io::Result<Vec<Entry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut entries = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.rsplitn(3, '|');
+        let time = fields.next().and_then(|time| time.parse().ok());
+        let rank = fields.next().and_then(|rank| rank.parse().ok());
+        if let (Some(time), Some(rank), Some(path)) = (time, rank, fields.next()) {
+            entries.push(Entry {
+                path: path.to_string(),
+                rank,
+                time,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+fn save(path: &str, entries: &[Entry]) -> io::Result<()> {
+    let content: String = entries.iter()
+        .map(|entry| format!("{}|{}|{}\n", entry.path, entry.rank, entry.time))
+        .collect();
+    replace_private(path, |mut file| file.write_all(content.as_bytes()))
+}
+
+fn lock(path: &str) -> io::Result<File> {
+    open_locked(path, OpenOptions::new().create(true).read(true).write(true), libc::LOCK_EX)
+}
+
+pub fn visit(path: &str, dir: &str, now: i64) -> io::Result<()> {
+    let _lock = lock(path)?;
+    let mut entries = load(path)?;
+    match entries.iter_mut().find(|entry| entry.path == dir) {
+        Some(entry) => {
+            entry.rank += 1.0;
+            entry.time = now;
+        },
+        None => entries.push(Entry {
+            path: dir.to_string(),
+            rank: 1.0,
+            time: now,
+        }),
+    }
+    if entries.iter().map(|entry| entry.rank).sum::<f64>() > MAX_RANK {
+        for entry in &mut entries {
+            entry.rank *= 0.99;
+        }
+        entries.retain(|entry| entry.rank >= 1.0);
+    }
+    save(path, &entries)
+}
+
+pub fn forget(path: &str, dir: &str) -> io::Result<()> {
+    let _lock = lock(path)?;
+    let mut entries = load(path)?;
+    entries.retain(|entry| entry.path != dir);
+    save(path, &entries)
+}
+
+pub fn search(path: &str, words: &[String], now: i64) -> Vec<Entry> {
+    let mut entries = load(path).unwrap_or_default();
+    entries.retain(|entry| entry.matches(words));
+    entries.sort_by(|a, b| b.score(now).partial_cmp(&a.score(now)).unwrap_or(Ordering::Equal));
+    entries
+}
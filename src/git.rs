@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use sys::output_timeout;
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+fn git_dir(dir: &str) -> Option<PathBuf> {
+    let mut dir = PathBuf::from(dir);
+    loop {
+        let git = dir.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        if let Ok(link) = fs::read_to_string(&git) {
+            let path = link.trim().strip_prefix("gitdir:")?.trim().to_string();
+            return Some(dir.join(path));
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+fn head(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(reference.trim_start_matches("refs/heads/").to_string()),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+fn status(dir: &str) -> Option<String> {
+    let (status, output) = output_timeout(Command::new("git")
+        .args(["status", "--porcelain=v2", "--branch"])
+        .current_dir(dir)
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null()), TIMEOUT)?;
+    if !status.success() {
+        return None;
+    }
+    String::from_utf8(output).ok()
+}
+
+pub fn prompt(dir: &str) -> String {
+    let git_dir = match git_dir(dir) {
+        Some(git_dir) => git_dir,
+        None => return String::new(),
+    };
+    let mut branch = head(&git_dir).unwrap_or_default();
+    let output = match status(dir) {
+        Some(output) => output,
+        None => return branch,
+    };
+    let (mut staged, mut unstaged, mut untracked) = (false, false, false);
+    let (mut ahead, mut behind) = (0, 0);
+    for line in output.lines() {
+        let mut fields = line.split(' ');
+        match fields.next() {
+            Some("#") => match fields.next() {
+                Some("branch.head") => {
+                    match fields.next() {
+                        Some("(detached)") | None => {},
+                        Some(head) => branch = head.to_string(),
+                    }
+                },
+                Some("branch.ab") => {
+                    ahead = fields.next().and_then(|n| n.trim_start_matches('+').parse().ok()).unwrap_or(0);
+                    behind = fields.next().and_then(|n| n.trim_start_matches('-').parse().ok()).unwrap_or(0);
+                },
+                _ => {},
+            },
+            Some("1") | Some("2") | Some("u") => {
+                let xy: Vec<_> = fields.next().unwrap_or("..").chars().collect();
+                staged |= xy.first().is_some_and(|&x| x != '.');
+                unstaged |= xy.get(1).is_some_and(|&y| y != '.');
+            },
+            Some("?") => untracked = true,
+            _ => {},
+        }
+    }
+    if unstaged {
+        branch.push('*');
+    }
+    if staged {
+        branch.push('+');
+    }
+    if untracked {
+        branch.push('%');
+    }
+    if ahead > 0 {
+        branch.push_str(&format!(" ↑{}", ahead));
+    }
+    if behind > 0 {
+        branch.push_str(&format!(" ↓{}", behind));
+    }
+    branch
+}
@@ -0,0 +1,116 @@
+use expand::expand;
+use lexer::{is_assignment, tokenize};
+use shell::Rush;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+const SEPARATORS: &[&str] = &["|", "&", "&&", "||"];
+const REDIRECTS: &[&str] = &["<", ">"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Plain,
+    Command,
+    Unknown,
+    Quote,
+    Operator,
+    Path,
+    Ghost,
+}
+
+impl Style {
+    pub fn sgr(self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Command => "32",
+            Style::Unknown => "31",
+            Style::Quote => "33",
+            Style::Operator => "36",
+            Style::Path => "4",
+            Style::Ghost => "2",
+        }
+    }
+}
+
+fn is_executable(path: &str) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+fn quotes(word: &str) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in word.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if let Some((open, start)) = quote {
+            if c == open {
+                regions.push((start, i+1));
+                quote = None;
+            } else {
+                escaped = c == '\\' && open == '"';
+            }
+        } else if c == '\'' || c == '"' {
+            quote = Some((c, i));
+        } else {
+            escaped = c == '\\';
+        }
+    }
+    if let Some((_, start)) = quote {
+        regions.push((start, word.len()));
+    }
+    regions
+}
+
+pub fn highlight(line: &str, rush: &Rush) -> Vec<Style> {
+    let mut styles = vec![Style::Plain; line.len()];
+    let mut path_commands = None;
+    let mut start = true;
+    let mut command = true;
+    let mut keyword = false;
+    let mut prev = "";
+    for token in tokenize(line) {
+        let word = token.text;
+        let expanded = expand(word, rush).unwrap_or_default();
+        let style = if SEPARATORS.contains(&word) || REDIRECTS.contains(&word) {
+            Style::Operator
+        } else if REDIRECTS.contains(&prev) {
+            if Path::new(&expanded).exists() { Style::Path } else { Style::Plain }
+        } else if start && word == "time" || keyword && word == "-p" {
+            Style::Command
+        } else if command && is_assignment(word) {
+            Style::Plain
+        } else if command {
+            let known = if expanded.contains('/') {
+                is_executable(&expanded)
+            } else {
+                rush.builtins.contains_key(expanded.as_str()) || rush.aliases.contains_key(word) ||
+                    path_commands.get_or_insert_with(|| rush.path_commands()).contains(&expanded)
+            };
+            if known { Style::Command } else { Style::Unknown }
+        } else if !expanded.is_empty() && Path::new(&expanded).exists() {
+            Style::Path
+        } else {
+            Style::Plain
+        };
+        for style_at in &mut styles[token.span.clone()] {
+            *style_at = style;
+        }
+        if style == Style::Plain {
+            for (from, to) in quotes(word) {
+                for style_at in &mut styles[token.span.start + from .. token.span.start + to] {
+                    *style_at = Style::Quote;
+                }
+            }
+        }
+        if !REDIRECTS.contains(&word) && !REDIRECTS.contains(&prev) {
+            let after_keyword = keyword;
+            keyword = start && word == "time";
+            command = SEPARATORS.contains(&word) || keyword || after_keyword && word == "-p";
+            start = SEPARATORS.contains(&word) && word != "|";
+        }
+        prev = word;
+    }
+    line.char_indices().map(|(i, _)| styles[i]).collect()
+}
@@ -0,0 +1,810 @@
+use libc::{self, c_int};
+use shell::Rush;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::process;
+use sys::{lock, time, Regex};
+
+const CHUNK: u64 = 64 * 1024;
+
+const SECRETS: &[&str] = &[
+    "(password|passwd|secret|token|api_?key)[[:space:]]*[=:]",
+    "authorization:[[:space:]]*(bearer|basic)",
+    "://[^/[:space:]:@]+:[^/[:space:]@]+@",
+    "(ghp|gho|ghu|ghs|ghr|github_pat)_[a-z0-9_]{20,}",
+    "akia[0-9a-z]{16}",
+    "xox[abprs]-[a-z0-9-]{10,}",
+    "sk-[a-z0-9_-]{20,}",
+    "-----begin [a-z ]*private key",
+];
+
+pub fn open_private(path: &str, options: &mut OpenOptions) -> io::Result<File> {
+    let file = options.mode(0o600).open(path)?;
+    let meta = file.metadata()?;
+    if meta.is_file() && meta.permissions().mode() & 0o077 != 0 {
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+pub fn create_private(path: &str) -> io::Result<File> {
+    open_private(path, OpenOptions::new().write(true).create(true).truncate(true))
+}
+
+pub fn replace_private<F: FnOnce(&File) -> io::Result<()>>(path: &str, write: F) -> io::Result<()> {
+    let tmp = format!("{}.{}", path, process::id());
+    let result = create_private(&tmp).and_then(|file| write(&file)).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+pub fn open_locked(path: &str, options: &OpenOptions, operation: c_int) -> io::Result<File> {
+    loop {
+        let file = open_private(path, &mut options.clone())?;
+        lock(&file, operation)?;
+        if fs::metadata(path).is_ok_and(|meta| meta.ino() == file.metadata().map_or(0, |meta| meta.ino())) {
+            return Ok(file);
+        }
+    }
+}
+
+pub fn write_lines<'a, I: IntoIterator<Item = &'a String>>(file: &File, lines: I) -> io::Result<()> {
+    let mut file = BufWriter::new(file);
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    file.flush()
+}
+
+pub fn read_lines<P: AsRef<Path>>(path: P) -> io::Result<Vec<String>> {
+    BufReader::new(File::open(path)?).lines().collect()
+}
+
+pub struct HistEntry {
+    pub line: String,
+    pub time: i64,
+}
+
+fn is_timestamp(line: &[u8]) -> bool {
+    line.len() > 1 && line[0] == b'#' && line[1 ..].iter().all(u8::is_ascii_digit)
+}
+
+fn parse_history<R: BufRead>(reader: R) -> io::Result<Vec<HistEntry>> {
+    let mut entries = Vec::new();
+    let mut time = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if is_timestamp(line.as_bytes()) {
+            time = line[1 ..].parse().unwrap_or(0);
+            continue;
+        }
+        entries.push(HistEntry {
+            line,
+            time,
+        });
+        time = 0;
+    }
+    Ok(entries)
+}
+
+pub fn write_history<'a, W, I>(writer: W, entries: I, timestamps: bool) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item = &'a HistEntry>
+{
+    let mut writer = BufWriter::new(writer);
+    for entry in entries {
+        if timestamps && entry.time != 0 {
+            writeln!(writer, "#{}", entry.time)?;
+        }
+        writeln!(writer, "{}", entry.line)?;
+    }
+    writer.flush()
+}
+
+pub fn read_history(path: &str) -> io::Result<(Vec<HistEntry>, u64)> {
+    let file = File::open(path)?;
+    lock(&file, libc::LOCK_SH)?;
+    let len = file.metadata()?.len();
+    Ok((parse_history(BufReader::new(file))?, len))
+}
+
+pub const FOREIGN_SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        match byte {
+            0x83 => result.extend(iter.next().map(|byte| byte ^ 0x20)),
+            byte => result.push(byte),
+        }
+    }
+    result
+}
+
+fn parse_zsh(content: &[u8]) -> Vec<HistEntry> {
+    let content = unmetafy(content);
+    let content = String::from_utf8_lossy(&content);
+    let mut entries = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let extended = line.strip_prefix(": ").and_then(|rest| rest.split_once(';')).and_then(|(meta, cmd)| {
+            meta.split(':').next()?.trim().parse().ok().map(|time| (time, cmd))
+        });
+        let (time, cmd) = extended.unwrap_or((0, line));
+        let mut line = cmd.to_owned();
+        while line.ends_with('\\') {
+            line.pop();
+            match lines.next() {
+                Some(next) => {
+                    line.push(' ');
+                    line.push_str(next);
+                },
+                None => break,
+            }
+        }
+        entries.push(HistEntry {
+            line,
+            time,
+        });
+    }
+    entries
+}
+
+fn parse_fish(content: &[u8]) -> Vec<HistEntry> {
+    let mut entries = Vec::new();
+    for line in String::from_utf8_lossy(content).lines() {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            let mut line = String::new();
+            let mut chars = cmd.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        chars.next();
+                        line.push(' ');
+                    },
+                    ('\\', Some('\\')) => {
+                        chars.next();
+                        line.push('\\');
+                    },
+                    (c, _) => line.push(c),
+                }
+            }
+            entries.push(HistEntry {
+                line,
+                time: 0,
+            });
+        } else if let (Some(when), Some(entry)) = (line.strip_prefix("  when: "), entries.last_mut()) {
+            entry.time = when.trim().parse().unwrap_or(0);
+        }
+    }
+    entries
+}
+
+pub fn parse_foreign(shell: &str, content: &[u8]) -> Option<Vec<HistEntry>> {
+    let mut entries = match shell {
+        "bash" => parse_history(content).ok()?,
+        "zsh" => parse_zsh(content),
+        "fish" => parse_fish(content),
+        _ => return None,
+    };
+    entries.retain(|entry| !entry.line.trim().is_empty());
+    Some(entries)
+}
+
+fn index_back(file: &mut File, end: u64, count: usize) -> io::Result<Vec<u64>> {
+    let mut starts = Vec::new();
+    let mut attach = false;
+    let mut buf = Vec::new();
+    let mut pos = end;
+    while pos > 0 {
+        let len = pos.min(CHUNK);
+        pos -= len;
+        let mut chunk = vec![0; len as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+        loop {
+            let content = buf.strip_suffix(b"\n").unwrap_or(&buf);
+            let start = match content.iter().rposition(|&b| b == b'\n') {
+                Some(i) => i + 1,
+                None if pos == 0 && !buf.is_empty() => 0,
+                None => break,
+            };
+            if is_timestamp(&content[start ..]) {
+                if attach {
+                    *starts.last_mut().unwrap() = pos + start as u64;
+                }
+                attach = false;
+            } else if starts.len() == count {
+                starts.reverse();
+                return Ok(starts);
+            } else {
+                starts.push(pos + start as u64);
+                attach = true;
+            }
+            buf.truncate(start);
+        }
+    }
+    starts.reverse();
+    Ok(starts)
+}
+
+fn read_tail(path: &str, size: usize, filesize: usize) -> io::Result<(Vec<HistEntry>, Vec<u64>, u64)> {
+    let count = size.max(filesize.saturating_add(1));
+    let mut file = open_locked(path, OpenOptions::new().read(true), libc::LOCK_SH)?;
+    let mut len = file.metadata()?.len();
+    let mut starts = index_back(&mut file, len, count)?;
+    if starts.len() > filesize {
+        drop(file);
+        file = open_locked(path, OpenOptions::new().read(true), libc::LOCK_EX)?;
+        len = file.metadata()?.len();
+        starts = index_back(&mut file, len, count)?;
+    }
+    let skip = starts.len().saturating_sub(filesize);
+    let mut cut = 0;
+    if skip > 0 {
+        cut = starts.get(skip).cloned().unwrap_or(len);
+        replace_private(path, |mut out| {
+            file.seek(SeekFrom::Start(cut))?;
+            io::copy(&mut file, &mut out)?;
+            Ok(())
+        })?;
+        starts.drain(.. skip);
+    }
+    let older = starts.len().saturating_sub(size);
+    let from = starts.get(older).cloned().unwrap_or(len);
+    starts.truncate(older);
+    starts.push(from);
+    file.seek(SeekFrom::Start(from))?;
+    let entries = parse_history(BufReader::new(&file))?;
+    Ok((entries, starts.into_iter().map(|start| start - cut).collect(), len - cut))
+}
+
+pub struct History {
+    pub entries: VecDeque<HistEntry>,
+    pub base: usize,
+    unsaved: usize,
+    pub offset: u64,
+    pub index: Vec<u64>,
+    secrets: Option<(String, Vec<Regex>)>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            base: 0,
+            unsaved: 0,
+            offset: 0,
+            index: Vec::new(),
+            secrets: None,
+        }
+    }
+
+    fn push(&mut self, line: String, size: usize) {
+        self.entries.push_back(HistEntry {
+            line,
+            time: time(),
+        });
+        self.unsaved += 1;
+        self.truncate(size);
+    }
+
+    pub fn extend(&mut self, entries: Vec<HistEntry>, size: usize) {
+        let at = self.entries.len() - self.unsaved.min(self.entries.len());
+        for (i, entry) in entries.into_iter().enumerate() {
+            self.entries.insert(at + i, entry);
+        }
+        self.truncate(size);
+    }
+
+    fn last(&self) -> Option<&String> {
+        self.entries.back().map(|entry| &entry.line)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+        self.base = 0;
+        self.unsaved = 0;
+    }
+
+    pub fn remove(&mut self, hisno: usize) -> Option<String> {
+        if hisno <= self.base {
+            return None;
+        }
+        let index = hisno - self.base - 1;
+        let saved = self.entries.len().saturating_sub(self.unsaved);
+        let entry = self.entries.remove(index)?;
+        if index >= saved {
+            self.unsaved -= 1;
+        }
+        Some(entry.line)
+    }
+
+    pub fn append(&mut self, file: &mut File, timestamps: bool) -> io::Result<()> {
+        let unsaved = self.unsaved.min(self.entries.len());
+        write_history(&*file, self.entries.iter().skip(self.entries.len() - unsaved), timestamps)?;
+        self.unsaved = 0;
+        Ok(())
+    }
+
+    fn merge(&mut self, file: &mut File, size: usize) -> io::Result<()> {
+        let len = file.metadata()?.len();
+        if len > self.offset {
+            file.seek(SeekFrom::Start(self.offset))?;
+            let entries = parse_history(BufReader::new(&*file))?;
+            self.extend(entries, size);
+        }
+        self.offset = len;
+        Ok(())
+    }
+
+    fn save(&mut self, path: &str, timestamps: bool, share: Option<usize>) -> io::Result<()> {
+        let mut file = open_locked(path, OpenOptions::new().create(true).read(true).append(true), libc::LOCK_EX)?;
+        if let Some(size) = share {
+            self.merge(&mut file, size)?;
+        }
+        self.append(&mut file, timestamps)?;
+        self.offset = file.metadata()?.len();
+        Ok(())
+    }
+
+    fn sync(&mut self, path: &str, size: usize) -> io::Result<()> {
+        let mut file = open_locked(path, OpenOptions::new().read(true), libc::LOCK_SH)?;
+        self.merge(&mut file, size)
+    }
+
+    fn erase(&mut self, line: &str) {
+        let saved = self.entries.len().saturating_sub(self.unsaved);
+        let erased = self.entries.iter().skip(saved).filter(|entry| entry.line == line).count();
+        self.entries.retain(|entry| entry.line != line);
+        self.unsaved -= erased;
+    }
+
+    fn truncate(&mut self, size: usize) {
+        while self.entries.len() > size {
+            self.entries.pop_front();
+            self.base += 1;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &HistEntry)> {
+        self.entries.iter().enumerate().map(move |(i, entry)| (self.base + i + 1, entry))
+    }
+
+    pub fn get(&self, hisno: usize) -> Option<&String> {
+        if hisno <= self.base {
+            None
+        } else {
+            self.entries.get(hisno - self.base - 1).map(|entry| &entry.line)
+        }
+    }
+
+    pub fn resolve(&self, spec: &str) -> Option<usize> {
+        let last = self.base + self.entries.len();
+        if self.entries.is_empty() {
+            return None;
+        }
+        let hisno = match spec.parse::<isize>() {
+            Ok(n) if n < 0 => last.saturating_sub(n.unsigned_abs() - 1).max(self.base + 1),
+            Ok(n) => (n as usize).clamp(self.base + 1, last),
+            Err(_) => {
+                let index = self.entries.iter().rposition(|entry| entry.line.starts_with(spec))?;
+                self.base + index + 1
+            },
+        };
+        self.get(hisno).map(|_| hisno)
+    }
+
+    pub fn search_file(&self, path: &str, query: &str, before: usize) -> io::Result<Option<(usize, String)>> {
+        if self.index.len() < 2 {
+            return Ok(None);
+        }
+        let mut file = File::open(path)?;
+        let count = (self.index.len() - 1).min(before);
+        let mut buf = Vec::new();
+        let mut pos = self.index[count];
+        for i in (0 .. count).rev() {
+            let (start, end) = (self.index[i], self.index[i+1]);
+            buf.truncate((end - pos) as usize);
+            if start < pos {
+                let from = start.min(pos.saturating_sub(CHUNK)).max(self.index[0]);
+                let mut chunk = vec![0; (pos - from) as usize];
+                file.seek(SeekFrom::Start(from))?;
+                file.read_exact(&mut chunk)?;
+                chunk.extend_from_slice(&buf);
+                buf = chunk;
+                pos = from;
+            }
+            let entry = &buf[(start - pos) as usize .. (end - pos) as usize];
+            let entry = entry.strip_suffix(b"\n").unwrap_or(entry);
+            let line = match entry.iter().position(|&b| b == b'\n') {
+                Some(pos) => &entry[pos+1 ..],
+                None => entry,
+            };
+            let line = String::from_utf8_lossy(line);
+            if line.contains(query) {
+                return Ok(Some((i, line.into_owned())));
+            }
+        }
+        Ok(None)
+    }
+
+    fn find<P: Fn(&str) -> bool>(&self, pred: P) -> Option<&String> {
+        self.entries.iter().rev().map(|entry| &entry.line).find(|line| pred(line))
+    }
+
+    fn event(&self, spec: &str) -> Result<Option<(&String, usize)>, String> {
+        let (event, len) = match spec.chars().next() {
+            None => return Ok(None),
+            Some(c) if c.is_whitespace() || c == '=' || c == '(' => return Ok(None),
+            Some('!') => (self.last(), 1),
+            Some(c) if "^$*:".contains(c) => (self.last(), 0),
+            Some('?') => {
+                let (needle, len) = match spec[1 ..].find('?') {
+                    Some(end) => (&spec[1 .. end+1], end+2),
+                    None => (&spec[1 ..], spec.len()),
+                };
+                (self.find(|line| line.contains(needle)), len)
+            },
+            Some(_) => {
+                let len = spec.find(|c: char| c.is_whitespace() || c == ':' || c == '"').unwrap_or(spec.len());
+                let word = &spec[.. len];
+                let event = match word.parse::<isize>() {
+                    Ok(n) if n > 0 => self.get(n as usize),
+                    Ok(n) if n < 0 => {
+                        let last = self.base + self.entries.len();
+                        last.checked_sub(n.unsigned_abs() - 1).and_then(|hisno| self.get(hisno))
+                    },
+                    Ok(_) => None,
+                    Err(_) => self.find(|line| line.starts_with(word)),
+                };
+                (event, len)
+            },
+        };
+        match event {
+            Some(event) => Ok(Some((event, len))),
+            None => Err(format!("!{}: event not found", &spec[.. len])),
+        }
+    }
+
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        if line.starts_with('^') {
+            return self.expand(&format!("!!:s{}", line));
+        }
+        let mut result = String::new();
+        let mut rest = line;
+        let mut expanded = false;
+        let mut quote = None;
+        while let Some(pos) = find_event(rest, &mut quote) {
+            result.push_str(&rest[.. pos]);
+            rest = &rest[pos+1 ..];
+            let spec = rest;
+            let (event, len) = match self.event(spec)? {
+                Some(event) => event,
+                None => {
+                    result.push('!');
+                    continue;
+                },
+            };
+            rest = &rest[len ..];
+            let mut text = event.to_owned();
+            let designator = match rest.strip_prefix(':') {
+                Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit() || "^$*-".contains(c)) => Some(rest),
+                Some(_) => None,
+                None if rest.starts_with(|c: char| "^$*".contains(c)) => Some(rest),
+                None => None,
+            };
+            if let Some(designator) = designator {
+                let words: Vec<_> = event.split_whitespace().collect();
+                match select_words(&words, designator) {
+                    Some((words, len)) => {
+                        text = words;
+                        rest = &designator[len ..];
+                    },
+                    None => {
+                        let end = spec.find(char::is_whitespace).unwrap_or(spec.len());
+                        return Err(format!("!{}: bad word specifier", &spec[.. end]));
+                    },
+                }
+            }
+            while rest.starts_with(':') {
+                let len = modify(&mut text, &rest[1 ..])?;
+                rest = &rest[1+len ..];
+            }
+            result.push_str(&text);
+            expanded = true;
+        }
+        result.push_str(rest);
+        Ok(if expanded { Some(result) } else { None })
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn find_event(text: &str, quote: &mut Option<char>) -> Option<usize> {
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (c, *quote) {
+            ('\'', None) | ('"', None) => *quote = Some(c),
+            (c, Some(open)) if c == open => *quote = None,
+            (_, Some('\'')) => {},
+            ('\\', _) => {
+                chars.next();
+            },
+            ('!', Some('"')) if text[i+1 ..].starts_with('"') => {},
+            ('!', _) => return Some(i),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn select_words(words: &[&str], spec: &str) -> Option<(String, usize)> {
+    let last = words.len().checked_sub(1)?;
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (first, mut len) = match spec.chars().next()? {
+        '^' => (1, 1),
+        '$' => return Some((words[last].to_owned(), 1)),
+        '*' => return Some((words[1 ..].join(" "), 1)),
+        '-' => (0, 0),
+        _ => {
+            let n = digits(spec);
+            (spec[.. n].parse().ok()?, n)
+        },
+    };
+    let rest = &spec[len ..];
+    let end = if rest.starts_with('*') {
+        len += 1;
+        if first > last {
+            return Some((String::new(), len));
+        }
+        last
+    } else if let Some(rest) = rest.strip_prefix('-') {
+        len += 1;
+        if rest.starts_with('$') {
+            len += 1;
+            last
+        } else {
+            let n = digits(rest);
+            if n == 0 {
+                last.checked_sub(1)?
+            } else {
+                len += n;
+                rest[.. n].parse().ok()?
+            }
+        }
+    } else {
+        first
+    };
+    if first > end || end > last {
+        return None;
+    }
+    Some((words[first ..= end].join(" "), len))
+}
+
+fn modify(text: &mut String, spec: &str) -> Result<usize, String> {
+    let slash = text.rfind('/');
+    match spec.chars().next() {
+        Some('h') => {
+            if let Some(pos) = slash {
+                text.truncate(pos);
+            }
+            Ok(1)
+        },
+        Some('t') => {
+            if let Some(pos) = slash {
+                *text = text[pos+1 ..].to_owned();
+            }
+            Ok(1)
+        },
+        Some(c) if c == 'r' || c == 'e' => {
+            if let Some(pos) = text.rfind('.') {
+                if slash.is_none_or(|slash| pos > slash) {
+                    if c == 'r' {
+                        text.truncate(pos);
+                    } else {
+                        *text = text[pos ..].to_owned();
+                    }
+                }
+            }
+            Ok(1)
+        },
+        Some('s') => {
+            let delim = match spec[1 ..].chars().next() {
+                Some(delim) => delim,
+                None => return Err("s: missing delimiter".to_owned()),
+            };
+            let mut parts = spec[1+delim.len_utf8() ..].splitn(3, delim);
+            let old = parts.next().unwrap_or("");
+            let new = parts.next().unwrap_or("");
+            let len = 1 + delim.len_utf8() + old.len() + delim.len_utf8() + new.len() +
+                if parts.next().is_some() { delim.len_utf8() } else { 0 };
+            if old.is_empty() || !text.contains(old) {
+                return Err(format!("s{}{}{}{}: substitution failed", delim, old, delim, new));
+            }
+            *text = text.replacen(old, &new.replace('&', old), 1);
+            Ok(len.min(spec.len()))
+        },
+        _ => Err(format!(":{}: unrecognized history modifier", spec.chars().next().unwrap_or(' '))),
+    }
+}
+
+impl Rush {
+    pub fn histfile(&self) -> Option<String> {
+        match self.vars.get("HISTFILE") {
+            Some(path) => Some(path.to_owned()),
+            None => match self.profile() {
+                Some(profile) => self.data_file(&format!("history-{}", profile), None),
+                None => self.data_file("history", Some(".rush_history")),
+            },
+        }
+    }
+
+    pub fn histsize(&self, name: &str) -> Option<usize> {
+        self.vars.get(name).and_then(|size| size.parse().ok())
+    }
+
+    pub fn load_history(&mut self) {
+        let path = match self.histfile() {
+            Some(path) => path,
+            None => return,
+        };
+        let size = self.histsize("HISTSIZE").unwrap_or(1000);
+        let filesize = self.histsize("HISTFILESIZE").unwrap_or(size);
+        let (entries, index, len) = match read_tail(&path, size, filesize) {
+            Ok(history) => history,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => {
+                if self.interactive && !self.prompted {
+                    self.import_first_run();
+                }
+                return;
+            },
+            Err(error) => {
+                eprintln!("I/O Error: {}: {}", path, error);
+                return;
+            },
+        };
+        self.history.offset = len;
+        self.history.index = index;
+        self.history.extend(entries, size);
+    }
+
+    pub fn foreign_histfile(&self, shell: &str) -> Option<String> {
+        let home = self.vars.get("HOME")?;
+        match shell {
+            "bash" => Some(format!("{}/.bash_history", home)),
+            "zsh" => Some(format!("{}/.zsh_history", self.vars.get("ZDOTDIR").unwrap_or(home))),
+            "fish" => match self.vars.get("XDG_DATA_HOME") {
+                Some(data) => Some(format!("{}/fish/fish_history", data)),
+                None => Some(format!("{}/.local/share/fish/fish_history", home)),
+            },
+            _ => None,
+        }
+    }
+
+    pub fn import_history(&mut self, entries: &[HistEntry]) -> io::Result<()> {
+        let path = match self.histfile() {
+            Some(path) => path,
+            None => return Err(io::Error::new(ErrorKind::NotFound, "HISTFILE is not set")),
+        };
+        self.save_history()?;
+        let mut file = open_locked(&path, OpenOptions::new().create(true).read(true).write(true), libc::LOCK_EX)?;
+        let mut existing = Vec::new();
+        file.read_to_end(&mut existing)?;
+        replace_private(&path, |mut out| {
+            write_history(out, entries, true)?;
+            out.write_all(&existing)
+        })?;
+        drop(file);
+        self.history.clear();
+        self.load_history();
+        Ok(())
+    }
+
+    fn import_first_run(&mut self) {
+        let mut entries = Vec::new();
+        for shell in FOREIGN_SHELLS {
+            let path = match self.foreign_histfile(shell) {
+                Some(path) => path,
+                None => continue,
+            };
+            if let Some(imported) = fs::read(&path).ok().and_then(|content| parse_foreign(shell, &content)) {
+                eprintln!("history: imported {} entries from {}", imported.len(), path);
+                entries.extend(imported);
+            }
+        }
+        if !entries.is_empty() {
+            if let Err(error) = self.import_history(&entries) {
+                eprintln!("history: import failed: {}", error);
+            }
+        }
+    }
+
+    pub fn save_history(&mut self) -> io::Result<()> {
+        let path = match self.histfile() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let timestamps = self.vars.get("HISTTIMEFORMAT").is_some();
+        let size = self.histsize("HISTSIZE").unwrap_or(1000);
+        let share = if self.options.sharehistory { Some(size) } else { None };
+        self.history.save(&path, timestamps, share)
+    }
+
+    pub fn sync_history(&mut self) {
+        if let Some(path) = self.histfile() {
+            let size = self.histsize("HISTSIZE").unwrap_or(1000);
+            let _ = self.history.sync(&path, size);
+        }
+    }
+
+    fn is_secret(&mut self, line: &str) -> bool {
+        let patterns = self.vars.get("HISTSECRETS").map_or_else(|| SECRETS.join("\n"), str::to_string);
+        if self.history.secrets.as_ref().map(|secrets| &secrets.0) != Some(&patterns) {
+            let mut regexes = Vec::new();
+            for pattern in patterns.lines().filter(|pattern| !pattern.is_empty()) {
+                match Regex::new(pattern) {
+                    Some(regex) => regexes.push(regex),
+                    None => eprintln!("history: {}: invalid secret pattern", pattern),
+                }
+            }
+            self.history.secrets = Some((patterns, regexes));
+        }
+        self.history.secrets.as_ref().is_some_and(|secrets| secrets.1.iter().any(|regex| regex.is_match(line)))
+    }
+
+    pub fn add_history(&mut self, line: String) {
+        if !self.options.history || self.options.private || self.is_secret(&line) {
+            return;
+        }
+        let mut ignorespace = false;
+        let mut ignoredups = false;
+        let mut erasedups = false;
+        for control in self.vars.get("HISTCONTROL").unwrap_or("").split(':') {
+            match control {
+                "ignorespace" => ignorespace = true,
+                "ignoredups" => ignoredups = true,
+                "ignoreboth" => {
+                    ignorespace = true;
+                    ignoredups = true;
+                },
+                "erasedups" => erasedups = true,
+                _ => {},
+            }
+        }
+        if ignorespace && line.starts_with(' ') {
+            return;
+        }
+        if ignoredups && self.history.last() == Some(&line) {
+            return;
+        }
+        for pattern in self.vars.get("HISTIGNORE").unwrap_or("").split(':') {
+            let ignored = match pattern {
+                "" => false,
+                "&" => self.history.last() == Some(&line),
+                pattern => self.patterns.get(pattern).matches(&line),
+            };
+            if ignored {
+                return;
+            }
+        }
+        if erasedups {
+            self.history.erase(&line);
+        }
+        let size = self.histsize("HISTSIZE").unwrap_or(1000);
+        self.history.push(line, size);
+        let _ = self.save_history();
+    }
+}
@@ -0,0 +1,144 @@
+use libc::{self, c_int, pid_t};
+use std::fmt;
+use sys::{exit_status, time, Sys};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Running,
+    Stopped,
+    Done(c_int),
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            State::Running => f.pad("Running"),
+            State::Stopped => f.pad("Stopped"),
+            State::Done(0) => f.pad("Done"),
+            State::Done(status) => f.pad(&format!("Exit {}", status)),
+        }
+    }
+}
+
+pub struct Job {
+    pub id: usize,
+    pub pids: Vec<pid_t>,
+    pub cmd: String,
+    pub state: State,
+    pub started: i64,
+    status: c_int,
+    reaped: Vec<pid_t>,
+}
+
+impl Job {
+    pub fn live_pids(&self) -> impl Iterator<Item = pid_t> + '_ {
+        self.pids.iter().cloned().filter(move |pid| !self.reaped.contains(pid))
+    }
+
+    fn record(&mut self, pid: pid_t, status: c_int) {
+        if libc::WIFSTOPPED(status) {
+            self.state = State::Stopped;
+        } else if libc::WIFCONTINUED(status) {
+            self.state = State::Running;
+        } else {
+            self.reaped.push(pid);
+            if self.pids.last() == Some(&pid) {
+                self.status = exit_status(status);
+            }
+            if self.live_pids().next().is_none() {
+                self.state = State::Done(self.status);
+            }
+        }
+    }
+}
+
+pub struct Jobs {
+    jobs: Vec<Job>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, pids: Vec<pid_t>, cmd: String) {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            pids,
+            cmd,
+            state: State::Running,
+            started: time(),
+            status: 0,
+            reaped: Vec::new(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn find(&self, spec: &str) -> Option<&Job> {
+        if let Ok(id) = spec.parse() {
+            return self.get(id);
+        }
+        let mut jobs = self.jobs.iter().filter(|job| !spec.is_empty() && job.cmd.starts_with(spec));
+        match (jobs.next(), jobs.next()) {
+            (Some(job), None) => Some(job),
+            _ => None,
+        }
+    }
+
+    pub fn update<S: Sys>(&mut self, sys: &mut S) {
+        loop {
+            let mut status = 0;
+            let pid = sys.waitpid(0, &mut status, libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED);
+            if pid <= 0 {
+                break;
+            }
+            if let Some(job) = self.jobs.iter_mut().find(|job| job.live_pids().any(|live| live == pid)) {
+                job.record(pid, status);
+            }
+        }
+    }
+
+    pub fn reap(&mut self) -> Vec<Job> {
+        let (done, running) = self.jobs.drain(..).partition(|job| matches!(job.state, State::Done(_)));
+        self.jobs = running;
+        done
+    }
+
+    pub fn pids(&self) -> impl Iterator<Item = pid_t> + '_ {
+        self.jobs.iter().flat_map(Job::live_pids)
+    }
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn wait<S: Sys>(sys: &mut S, pids: Vec<pid_t>) -> Option<c_int> {
+    let mut result = None;
+    for pid in pids {
+        let mut status = 0;
+        sys.waitpid(pid, &mut status, 0);
+        result = Some(status);
+    }
+    result
+}
@@ -0,0 +1,462 @@
+use editor::{control_key, escape_key, Key};
+use shell::Rush;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str;
+
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    AcceptLine,
+    BackwardChar,
+    BackwardDeleteChar,
+    BackwardWord,
+    BeginningOfLine,
+    ClearLine,
+    ClearScreen,
+    Complete,
+    DeleteChar,
+    EditCommandLine,
+    EndOfLine,
+    ForwardChar,
+    ForwardWord,
+    HistorySearchBackward,
+    KillLine,
+    KillWholeLine,
+    KillWord,
+    NextHistory,
+    PreviousHistory,
+    ReverseSearchHistory,
+    SelfInsert,
+    SendBreak,
+    TransposeChars,
+    Undo,
+    UnixLineDiscard,
+    UnixWordRubout,
+    Yank,
+    YankLastArg,
+    YankPop,
+}
+
+pub const ACTIONS: &[(&str, Action)] = &[
+    ("accept-line", Action::AcceptLine),
+    ("backward-char", Action::BackwardChar),
+    ("backward-delete-char", Action::BackwardDeleteChar),
+    ("backward-word", Action::BackwardWord),
+    ("beginning-of-line", Action::BeginningOfLine),
+    ("clear-line", Action::ClearLine),
+    ("clear-screen", Action::ClearScreen),
+    ("complete", Action::Complete),
+    ("delete-char", Action::DeleteChar),
+    ("edit-command-line", Action::EditCommandLine),
+    ("end-of-line", Action::EndOfLine),
+    ("forward-char", Action::ForwardChar),
+    ("forward-word", Action::ForwardWord),
+    ("history-search-backward", Action::HistorySearchBackward),
+    ("kill-line", Action::KillLine),
+    ("kill-whole-line", Action::KillWholeLine),
+    ("kill-word", Action::KillWord),
+    ("next-history", Action::NextHistory),
+    ("previous-history", Action::PreviousHistory),
+    ("reverse-search-history", Action::ReverseSearchHistory),
+    ("self-insert", Action::SelfInsert),
+    ("send-break", Action::SendBreak),
+    ("transpose-chars", Action::TransposeChars),
+    ("undo", Action::Undo),
+    ("unix-line-discard", Action::UnixLineDiscard),
+    ("unix-word-rubout", Action::UnixWordRubout),
+    ("yank", Action::Yank),
+    ("yank-last-arg", Action::YankLastArg),
+    ("yank-pop", Action::YankPop),
+];
+
+const DEFAULTS: &[(&str, Action)] = &[
+    ("\\C-a", Action::BeginningOfLine),
+    ("\\C-b", Action::BackwardChar),
+    ("\\C-c", Action::SendBreak),
+    ("\\C-d", Action::DeleteChar),
+    ("\\C-e", Action::EndOfLine),
+    ("\\C-f", Action::ForwardChar),
+    ("\\C-i", Action::Complete),
+    ("\\C-k", Action::KillLine),
+    ("\\C-l", Action::ClearScreen),
+    ("\\C-m", Action::AcceptLine),
+    ("\\C-n", Action::NextHistory),
+    ("\\C-p", Action::PreviousHistory),
+    ("\\C-r", Action::ReverseSearchHistory),
+    ("\\C-t", Action::TransposeChars),
+    ("\\C-u", Action::UnixLineDiscard),
+    ("\\C-w", Action::UnixWordRubout),
+    ("\\C-y", Action::Yank),
+    ("\\C-_", Action::Undo),
+    ("\\C-?", Action::BackwardDeleteChar),
+    ("\\C-x\\C-e", Action::EditCommandLine),
+    ("\\C-x\\C-u", Action::Undo),
+    ("\\e.", Action::YankLastArg),
+    ("\\e/", Action::HistorySearchBackward),
+    ("\\eb", Action::BackwardWord),
+    ("\\ed", Action::KillWord),
+    ("\\ef", Action::ForwardWord),
+    ("\\ey", Action::YankPop),
+    ("\\e[A", Action::PreviousHistory),
+    ("\\e[B", Action::NextHistory),
+    ("\\e[C", Action::ForwardChar),
+    ("\\e[D", Action::BackwardChar),
+    ("\\e[F", Action::EndOfLine),
+    ("\\e[H", Action::BeginningOfLine),
+    ("\\e[3~", Action::DeleteChar),
+];
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS.iter().find(|entry| entry.1 == action).map_or("", |entry| entry.0)
+}
+
+fn control(c: char) -> Result<u8, String> {
+    match c {
+        '?' => Ok(0x7f),
+        c if c.is_ascii() => Ok(c.to_ascii_lowercase() as u8 & 0x1f),
+        c => Err(format!("\\C-{}: invalid control character", c)),
+    }
+}
+
+fn parse_seq(seq: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = seq.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).bytes());
+            continue;
+        }
+        match chars.next() {
+            Some(prefix @ ('C' | 'M')) if chars.peek() == Some(&'-') => {
+                chars.next();
+                let c = match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('e') => '\x1b',
+                        Some(c) => c,
+                        None => '\\',
+                    },
+                    Some(c) => c,
+                    None => return Err(format!("{}: incomplete key sequence", seq)),
+                };
+                if prefix == 'C' {
+                    bytes.push(control(c)?);
+                } else {
+                    let mut buf = [0; 4];
+                    bytes.push(0x1b);
+                    bytes.extend(c.encode_utf8(&mut buf).bytes());
+                }
+            },
+            Some('e') => bytes.push(0x1b),
+            Some('a') => bytes.push(0x07),
+            Some('b') => bytes.push(0x08),
+            Some('d') => bytes.push(0x7f),
+            Some('f') => bytes.push(0x0c),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('v') => bytes.push(0x0b),
+            Some(digit @ '0' ..= '7') => {
+                let mut value = digit.to_digit(8).unwrap();
+                for _ in 0 .. 2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        },
+                        None => break,
+                    }
+                }
+                bytes.push(value as u8);
+            },
+            Some('x') => {
+                let mut value = 0;
+                for _ in 0 .. 2 {
+                    match chars.peek().and_then(|c| c.to_digit(16)) {
+                        Some(digit) => {
+                            value = value * 16 + digit;
+                            chars.next();
+                        },
+                        None => break,
+                    }
+                }
+                bytes.push(value as u8);
+            },
+            Some(c) => {
+                let mut buf = [0; 4];
+                bytes.extend(c.encode_utf8(&mut buf).bytes());
+            },
+            None => return Err(format!("{}: incomplete key sequence", seq)),
+        }
+    }
+    Ok(bytes)
+}
+
+fn parse_keyname(name: &str) -> Result<Vec<u8>, String> {
+    let mut rest = name;
+    let mut ctrl = false;
+    let mut meta = false;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        if let Some(prefix) = ["control-", "c-"].iter().find(|prefix| lower.starts_with(*prefix) && rest.len() > prefix.len()) {
+            ctrl = true;
+            rest = &rest[prefix.len() ..];
+        } else if let Some(prefix) = ["meta-", "m-"].iter().find(|prefix| lower.starts_with(*prefix) && rest.len() > prefix.len()) {
+            meta = true;
+            rest = &rest[prefix.len() ..];
+        } else {
+            break;
+        }
+    }
+    let byte = match rest.to_ascii_lowercase().as_str() {
+        "del" | "rubout" => 0x7f,
+        "esc" | "escape" => 0x1b,
+        "lfd" | "newline" => b'\n',
+        "ret" | "return" => b'\r',
+        "spc" | "space" => b' ',
+        "tab" => b'\t',
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if ctrl => control(c)?,
+                (Some(c), None) if c.is_ascii() => c as u8,
+                _ => return Err(format!("{}: unknown key name", name)),
+            }
+        },
+    };
+    let byte = if ctrl && byte != 0x7f && byte >= 0x20 { byte & 0x1f } else { byte };
+    Ok(if meta { vec![0x1b, byte] } else { vec![byte] })
+}
+
+fn single_char(bytes: &[u8]) -> Option<char> {
+    let mut chars = str::from_utf8(bytes).ok()?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+fn decode(bytes: &[u8]) -> Option<Key> {
+    let key = match bytes {
+        [0x1b] => Key::Esc,
+        [0x1b, b'[' | b'O', seq @ ..] => escape_key(seq),
+        [0x1b, rest @ ..] => Key::Alt(single_char(rest)?),
+        [0x18] => return None,
+        [0x18, byte] if *byte < 0x20 => match control_key(*byte) {
+            Key::Ctrl(c) => Key::CtrlX(c),
+            _ => return None,
+        },
+        &[byte] if byte < 0x20 || byte == 0x7f => control_key(byte),
+        _ => Key::Char(single_char(bytes)?),
+    };
+    if key == Key::Unknown { None } else { Some(key) }
+}
+
+fn char_notation(c: char) -> String {
+    match c {
+        '"' => "\\\"".to_owned(),
+        '\\' => "\\\\".to_owned(),
+        '\x7f' => "\\C-?".to_owned(),
+        c if c.is_ascii_control() => match c as u8 {
+            byte @ 0x01 ..= 0x1a => format!("\\C-{}", (byte + 0x60) as char),
+            byte => format!("\\C-{}", (byte + 0x40) as char),
+        },
+        c => c.to_string(),
+    }
+}
+
+fn notation(key: &Key) -> String {
+    match *key {
+        Key::Char(c) => char_notation(c),
+        Key::Ctrl(c) => format!("\\C-{}", c),
+        Key::CtrlX(c) => format!("\\C-x\\C-{}", c),
+        Key::Alt(c) => format!("\\e{}", char_notation(c)),
+        Key::Enter => "\\C-m".to_owned(),
+        Key::Tab => "\\C-i".to_owned(),
+        Key::BackTab => "\\e[Z".to_owned(),
+        Key::Backspace => "\\C-?".to_owned(),
+        Key::Delete => "\\e[3~".to_owned(),
+        Key::Esc => "\\e".to_owned(),
+        Key::Left => "\\e[D".to_owned(),
+        Key::Right => "\\e[C".to_owned(),
+        Key::Up => "\\e[A".to_owned(),
+        Key::Down => "\\e[B".to_owned(),
+        Key::Home => "\\e[H".to_owned(),
+        Key::End => "\\e[F".to_owned(),
+        Key::Paste(_) | Key::Unknown => String::new(),
+    }
+}
+
+fn parse_key(spec: &str) -> Result<Key, String> {
+    let bytes = match spec.strip_prefix('"').and_then(|spec| spec.strip_suffix('"')) {
+        Some(seq) => parse_seq(seq)?,
+        None => parse_keyname(spec)?,
+    };
+    decode(&bytes).ok_or_else(|| format!("{}: cannot bind this key sequence", spec))
+}
+
+fn split_binding(line: &str) -> Option<(&str, &str)> {
+    let end = if line.starts_with('"') {
+        let mut escaped = false;
+        let close = line.char_indices().skip(1).find(|&(_, c)| {
+            let found = c == '"' && !escaped;
+            escaped = c == '\\' && !escaped;
+            found
+        })?.0;
+        close + 1
+    } else {
+        line.find(':')?
+    };
+    let rest = line[end ..].trim_start().strip_prefix(':')?;
+    Some((&line[.. end], rest.trim()))
+}
+
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        for &(seq, action) in DEFAULTS {
+            if let Ok(key) = parse_key(&format!("\"{}\"", seq)) {
+                keymap.bindings.insert(key, action);
+            }
+        }
+        keymap
+    }
+
+    pub fn get(&self, key: &Key) -> Option<Action> {
+        match self.bindings.get(key) {
+            Some(&action) => Some(action),
+            None if matches!(key, Key::Char(_)) => Some(Action::SelfInsert),
+            None => None,
+        }
+    }
+
+    pub fn bind(&mut self, line: &str) -> Result<(), String> {
+        let (spec, function) = split_binding(line).ok_or_else(|| format!("{}: missing key or function name", line))?;
+        if function.starts_with(['"', '\'']) {
+            return Err(format!("{}: macros are not supported", spec));
+        }
+        let action = ACTIONS.iter().find(|entry| entry.0 == function)
+            .ok_or_else(|| format!("{}: unknown function name", function))?.1;
+        self.bindings.insert(parse_key(spec)?, action);
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, spec: &str) -> Result<(), String> {
+        let spec = if spec.starts_with('"') { spec.to_owned() } else { format!("\"{}\"", spec) };
+        self.bindings.remove(&parse_key(&spec)?);
+        Ok(())
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<_> = self.bindings.iter()
+            .map(|(key, &action)| (action_name(action), notation(key)))
+            .collect();
+        lines.sort();
+        lines.into_iter().map(|(name, seq)| format!("\"{}\": {}", seq, name)).collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rush {
+    fn inputrc_condition(&self, test: &str) -> bool {
+        match test.split_once('=') {
+            Some(("mode", mode)) => mode.trim() == if self.options.get("vi") == Some(true) { "vi" } else { "emacs" },
+            Some(("term", term)) => self.vars.get("TERM").is_some_and(|value| {
+                value == term.trim() || value.split('-').next() == Some(term.trim())
+            }),
+            Some(_) => false,
+            None => test.eq_ignore_ascii_case("rush"),
+        }
+    }
+
+    pub fn read_inputrc(&mut self, path: &str, depth: usize) -> io::Result<Vec<(String, usize, String)>> {
+        let text = fs::read_to_string(path)?;
+        let mut errors = Vec::new();
+        let mut conditions: Vec<bool> = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let active = conditions.iter().all(|&condition| condition);
+            let (word, rest) = line.split_once(char::is_whitespace).map_or((line, ""), |(word, rest)| (word, rest.trim()));
+            match word {
+                "$if" => {
+                    let condition = active && self.inputrc_condition(rest);
+                    conditions.push(condition);
+                    continue;
+                },
+                "$else" => {
+                    if let Some(condition) = conditions.pop() {
+                        let parent = conditions.iter().all(|&condition| condition);
+                        conditions.push(parent && !condition);
+                    }
+                    continue;
+                },
+                "$endif" => {
+                    conditions.pop();
+                    continue;
+                },
+                _ if !active => continue,
+                "$include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        errors.push((path.to_owned(), lineno+1, format!("{}: too many nested includes", rest)));
+                        continue;
+                    }
+                    let include = match rest.strip_prefix("~/") {
+                        Some(rest) => format!("{}/{}", self.vars.get("HOME").unwrap_or(""), rest),
+                        None => rest.to_owned(),
+                    };
+                    match self.read_inputrc(&include, depth + 1) {
+                        Ok(included) => errors.extend(included),
+                        Err(error) => errors.push((path.to_owned(), lineno+1, format!("{}: {}", include, error))),
+                    }
+                    continue;
+                },
+                "set" => {
+                    if let Some(("editing-mode", mode)) = rest.split_once(char::is_whitespace) {
+                        match mode.trim() {
+                            "vi" | "emacs" => {
+                                self.options.set(mode.trim(), true);
+                            },
+                            mode => errors.push((path.to_owned(), lineno+1, format!("{}: unknown editing mode", mode))),
+                        }
+                    }
+                    continue;
+                },
+                _ => {},
+            }
+            if let Err(error) = self.keymap.bind(line) {
+                errors.push((path.to_owned(), lineno+1, error));
+            }
+        }
+        Ok(errors)
+    }
+
+    pub fn load_inputrc(&mut self) {
+        let path = match self.vars.get("INPUTRC") {
+            Some(path) => path.to_owned(),
+            None => match self.config_file("inputrc", Some(".inputrc")) {
+                Some(path) if Path::new(&path).exists() => path,
+                _ => "/etc/inputrc".to_owned(),
+            },
+        };
+        let _ = self.read_inputrc(&path, 0);
+    }
+}
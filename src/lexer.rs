@@ -0,0 +1,83 @@
+use std::ops::Range;
+use std::str::CharIndices;
+
+pub fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        },
+        _ => false,
+    }
+}
+
+pub const RESERVED: &[&str] = &[
+    "!", "{", "}", "case", "do", "done", "elif", "else", "esac", "fi", "for", "if", "in", "then", "until", "while",
+];
+
+pub fn is_assignment(word: &str) -> bool {
+    match word.find('=') {
+        Some(pos) => is_name(&word[.. pos]),
+        None => false,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub span: Range<usize>,
+}
+
+pub struct Tokens<'a> {
+    line: &'a str,
+    chars: CharIndices<'a>,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let mut start = None;
+        let mut escaped = false;
+        let mut quote = None;
+        for (i, c) in self.chars.by_ref() {
+            if escaped {
+                escaped = false;
+            } else if let Some(open) = quote {
+                if c == open {
+                    quote = None;
+                } else {
+                    escaped = c == '\\' && open == '"';
+                }
+            } else if c.is_whitespace() {
+                match start {
+                    Some(start) => return Some(Token {
+                        text: &self.line[start .. i],
+                        span: start .. i,
+                    }),
+                    None => continue,
+                }
+            } else if c == '\'' || c == '"' {
+                quote = Some(c);
+            } else {
+                escaped = c == '\\';
+            }
+            start = start.or(Some(i));
+        }
+        start.map(|start| Token {
+            text: &self.line[start ..],
+            span: start .. self.line.len(),
+        })
+    }
+}
+
+pub fn tokenize(line: &str) -> Tokens<'_> {
+    Tokens {
+        line,
+        chars: line.char_indices(),
+    }
+}
+
+pub fn split_words(line: &str) -> Vec<&str> {
+    tokenize(line).map(|token| token.text).collect()
+}
@@ -0,0 +1,31 @@
+extern crate libc;
+
+pub mod alias;
+pub mod bash;
+pub mod builtins;
+mod color;
+mod complete;
+mod config;
+pub mod dirs;
+mod editor;
+pub mod error;
+pub mod exec;
+pub mod expand;
+mod frecency;
+pub mod git;
+mod highlight;
+mod keymap;
+pub mod history;
+pub mod jobs;
+pub mod lexer;
+pub mod lint;
+pub mod parser;
+pub mod pathindex;
+pub mod pattern;
+pub mod prompt;
+mod rushenv;
+mod session;
+pub mod shell;
+pub mod sys;
+mod theme;
+mod xdg;
@@ -0,0 +1,163 @@
+use lexer::is_name;
+use libc::c_int;
+use parser::{parse, AndOr, CmdLine};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+
+pub struct Lint {
+    pub line: usize,
+    pub message: String,
+}
+
+fn column(line: &str, word: &str) -> usize {
+    let offset = word.as_ptr() as usize - line.as_ptr() as usize;
+    line[.. offset].chars().count() + 1
+}
+
+fn references(word: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut quote = None;
+    let mut chars = word.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (c, quote) {
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (_, Some('\'')) => {},
+            ('\\', _) => {
+                chars.next();
+            },
+            ('$', _) => {
+                let rest = &word[i+1 ..];
+                let name = if rest.starts_with('{') {
+                    rest.find('}').map_or("", |end| &rest[1 .. end])
+                } else {
+                    let end = rest.find(|c: char| c != '_' && !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+                    &rest[.. end]
+                };
+                if is_name(name) {
+                    names.push(name);
+                }
+            },
+            _ => {},
+        }
+    }
+    names
+}
+
+fn cmdlines<'a, 'b>(list: &'b AndOr<'a>) -> impl Iterator<Item = &'b CmdLine<&'a str>> {
+    Some(&list.first).into_iter().chain(list.rest.iter().map(|(_, cmdline)| cmdline))
+}
+
+fn words<'a, 'b>(cmdline: &'b CmdLine<&'a str>) -> impl Iterator<Item = &'a str> + 'b {
+    cmdline.cmds.iter().flat_map(|cmd| cmd.cmd.iter().cloned()).chain(cmdline.filein).chain(cmdline.fileout)
+}
+
+fn terminates(list: &AndOr) -> bool {
+    if list.back || !list.rest.is_empty() || list.first.cmds.len() != 1 {
+        return false;
+    }
+    let cmd = &list.first.cmds[0];
+    cmd.prog() == "exit" || cmd.prog() == "exec" && cmd.cmd.len() > 1
+}
+
+pub fn lint(script: &str) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut lines = Vec::new();
+    for (lineno, line) in script.lines().enumerate() {
+        match parse(line) {
+            Ok(lists) => lines.push((lineno+1, line, lists)),
+            Err(error) => lints.push(Lint {
+                line: lineno+1,
+                message: error.to_string(),
+            }),
+        }
+    }
+    let mut assigned: HashSet<&str> = ["PWD"].iter().cloned().collect();
+    for list in lines.iter().flat_map(|(_, _, lists)| lists) {
+        for cmd in cmdlines(list).flat_map(|cmdline| &cmdline.cmds).filter(|cmd| cmd.is_assignment()) {
+            assigned.extend(cmd.cmd.iter().map(|word| &word[.. word.find('=').unwrap()]));
+        }
+    }
+    let mut exited = None;
+    let mut unreachable = false;
+    for &(lineno, line, ref lists) in &lines {
+        let mut truncated = HashSet::new();
+        for list in lists {
+            if let (Some(prog), false) = (exited, unreachable) {
+                if let Some(word) = cmdlines(list).flat_map(words).next() {
+                    lints.push(Lint {
+                        line: lineno,
+                        message: format!("unreachable command after {} (column {})", prog, column(line, word)),
+                    });
+                    unreachable = true;
+                }
+            }
+            for cmdline in cmdlines(list) {
+                for word in words(cmdline) {
+                    for name in references(word) {
+                        if !assigned.contains(name) && env::var_os(name).is_none() {
+                            lints.push(Lint {
+                                line: lineno,
+                                message: format!("{} is referenced but never assigned (column {})", name, column(line, word)),
+                            });
+                        }
+                    }
+                }
+                if let [ref cat, _, ..] = cmdline.cmds[..] {
+                    if cmdline.filein.is_none() && cat.prog() == "cat" && cat.cmd.len() == 2 && !cat.cmd[1].starts_with('-') {
+                        lints.push(Lint {
+                            line: lineno,
+                            message: format!("useless use of cat, redirect {} with < instead (column {})", cat.cmd[1], column(line, cat.cmd[0])),
+                        });
+                    }
+                }
+                if let Some(fileout) = cmdline.fileout {
+                    if cmdline.filein == Some(fileout) {
+                        lints.push(Lint {
+                            line: lineno,
+                            message: format!("{} is truncated before it is read (column {})", fileout, column(line, fileout)),
+                        });
+                    } else if !truncated.insert(fileout) {
+                        lints.push(Lint {
+                            line: lineno,
+                            message: format!("{} is truncated twice on the same line (column {})", fileout, column(line, fileout)),
+                        });
+                    }
+                }
+            }
+            if exited.is_none() && terminates(list) {
+                exited = Some(list.first.cmds[0].prog());
+            }
+        }
+    }
+    lints.sort_by_key(|lint| lint.line);
+    lints
+}
+
+pub fn run(paths: &[String]) -> c_int {
+    let mut status = 0;
+    let stdin = ["-".to_owned()];
+    for path in if paths.is_empty() { &stdin[..] } else { paths } {
+        let script = if path == "-" {
+            let mut script = String::new();
+            io::stdin().read_to_string(&mut script).map(|_| script)
+        } else {
+            fs::read_to_string(path)
+        };
+        let script = match script {
+            Ok(script) => script,
+            Err(error) => {
+                eprintln!("I/O Error: {}: {}", path, error);
+                status = 2;
+                continue;
+            },
+        };
+        for lint in lint(&script) {
+            println!("{}:{}: {}", path, lint.line, lint.message);
+            status = status.max(1);
+        }
+    }
+    status
+}
@@ -0,0 +1,230 @@
+use error::ShellError;
+use lexer::{is_assignment, tokenize, Token};
+use std::borrow::Borrow;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct Cmd<S> {
+    pub cmd: Vec<S>,
+}
+
+impl<S: Borrow<str>> Cmd<S> {
+    pub fn prog(&self) -> &str {
+        self.cmd.first().map_or("", Borrow::borrow)
+    }
+
+    pub fn is_assignment(&self) -> bool {
+        self.cmd.iter().all(|word| is_assignment(word.borrow()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Time {
+    Default,
+    Posix,
+}
+
+#[derive(Debug)]
+pub struct CmdLine<S> {
+    pub time: Option<Time>,
+    pub cmds: Vec<Cmd<S>>,
+    pub filein: Option<S>,
+    pub fileout: Option<S>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    And,
+    Or,
+}
+
+#[derive(Debug)]
+pub struct AndOr<'a> {
+    pub first: CmdLine<&'a str>,
+    pub rest: Vec<(Op, CmdLine<&'a str>)>,
+    pub back: bool,
+}
+
+fn error(line: &str, token: &Token, message: &str) -> ShellError {
+    let column = line[.. token.span.start].chars().count() + 1;
+    ShellError::Parse(format!("{} (column {})", message, column))
+}
+
+pub fn parse(line: &str) -> Result<Vec<AndOr<'_>>, ShellError> {
+    if line.contains('\0') {
+        return Err(ShellError::Parse("nul byte found in the input".to_owned()));
+    }
+    let tokens: Vec<_> = tokenize(line).collect();
+    let mut lists = Vec::new();
+    for tokens in tokens.split_inclusive(|token| token.text == "&") {
+        let (tokens, back) = match tokens.split_last() {
+            Some((last, tokens)) if last.text == "&" => {
+                if tokens.is_empty() {
+                    return Err(error(line, last, "& cannot appear as the first word in a command"));
+                }
+                (tokens, true)
+            },
+            _ => (tokens, false),
+        };
+        lists.push(AndOr::new(line, tokens, back)?);
+    }
+    Ok(lists)
+}
+
+fn op(token: &Token) -> Option<Op> {
+    match token.text {
+        "&&" => Some(Op::And),
+        "||" => Some(Op::Or),
+        _ => None,
+    }
+}
+
+impl<'a> AndOr<'a> {
+    fn new(line: &str, tokens: &[Token<'a>], back: bool) -> Result<Self, ShellError> {
+        for (i, token) in tokens.iter().enumerate().filter(|&(_, token)| op(token).is_some()) {
+            if i == 0 || op(&tokens[i-1]).is_some() {
+                return Err(error(line, token, &format!("{} cannot appear as the first word in a command", token.text)));
+            }
+            if i == tokens.len()-1 {
+                return Err(error(line, token, &format!("{} cannot appear as the last word in a command", token.text)));
+            }
+        }
+        let mut pipelines = tokens.split(|token| op(token).is_some());
+        let first = CmdLine::new(line, pipelines.next().unwrap_or(&[]))?;
+        let mut rest = Vec::new();
+        for (op, tokens) in tokens.iter().filter_map(op).zip(pipelines) {
+            rest.push((op, CmdLine::new(line, tokens)?));
+        }
+        Ok(Self {
+            first,
+            rest,
+            back,
+        })
+    }
+}
+
+impl<'a> CmdLine<&'a str> {
+    fn new(line: &str, tokens: &[Token<'a>]) -> Result<Self, ShellError> {
+        let time = match tokens {
+            [first, second, ..] if first.text == "time" && second.text == "-p" => Some(Time::Posix),
+            [first, ..] if first.text == "time" => Some(Time::Default),
+            _ => None,
+        };
+        let tokens = match time {
+            Some(Time::Posix) => &tokens[2 ..],
+            Some(Time::Default) => &tokens[1 ..],
+            None => tokens,
+        };
+        let mut top = true;
+        let mut cmds = Vec::new();
+        let mut filein = None;
+        let mut fileout = None;
+        let mut cmdno = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            let prev = if i == 0 { None } else { Some(tokens[i-1].text) };
+            let next = tokens.get(i+1).map(|token| token.text);
+            match token.text {
+                "|" => {
+                    if prev.is_none() || prev == Some("|") {
+                        return Err(error(line, token, "| cannot appear as the first word in a command"));
+                    }
+                    cmdno += 1;
+                    top = true;
+                }
+                "<" => {
+                    let next = match next {
+                        Some(next) => next,
+                        None => return Err(error(line, token, "No filename after <")),
+                    };
+                    if "&|<>".contains(next) {
+                        return Err(error(line, token, "Illegal filename after <"));
+                    }
+                    if cmdno > 0 {
+                        return Err(error(line, token, "< can appear only in the first command"));
+                    }
+                    filein = Some(next);
+                }
+                ">" => {
+                    let next = match next {
+                        Some(next) => next,
+                        None => return Err(error(line, token, "No filename after >")),
+                    };
+                    if "&|<>".contains(next) {
+                        return Err(error(line, token, "Illegal filename after >"));
+                    }
+                    if tokens[i+1 ..].iter().any(|token| token.text == "|") {
+                        return Err(error(line, token, "> can appear only in the last command"));
+                    }
+                    fileout = Some(next);
+                }
+                _ => {
+                    if prev != Some("<") && prev != Some(">") {
+                        if top {
+                            cmds.push(Cmd {
+                                cmd: Vec::new(),
+                            });
+                            top = false;
+                        }
+                        cmds[cmdno].cmd.push(token.text);
+                    }
+                },
+            }
+        }
+        if cmds.len() <= cmdno {
+            if let Some(pipe) = tokens.iter().rfind(|token| token.text == "|") {
+                return Err(error(line, pipe, "| cannot appear as the last word in a command"));
+            }
+        }
+        Ok(Self {
+            time,
+            cmds,
+            filein,
+            fileout,
+        })
+    }
+}
+
+impl<S> CmdLine<S> {
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+}
+
+impl<S: Borrow<str>> fmt::Display for CmdLine<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.time {
+            Some(Time::Default) => write!(f, "time ")?,
+            Some(Time::Posix) => write!(f, "time -p ")?,
+            None => {},
+        }
+        for (i, cmd) in self.cmds.iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{}", cmd.cmd.join(" "))?;
+            if i == 0 {
+                if let Some(ref path) = self.filein {
+                    write!(f, " < {}", path.borrow())?;
+                }
+            }
+        }
+        if let Some(ref path) = self.fileout {
+            write!(f, " > {}", path.borrow())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for AndOr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for &(op, ref cmdline) in &self.rest {
+            write!(f, " {} {}", if op == Op::And { "&&" } else { "||" }, cmdline)?;
+        }
+        Ok(())
+    }
+}
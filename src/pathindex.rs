@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+struct Dir {
+    mtime: Option<SystemTime>,
+    names: Vec<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct PathIndex {
+    dirs: Arc<Mutex<HashMap<String, Dir>>>,
+}
+
+fn mtime(dir: &str) -> Option<SystemTime> {
+    fs::metadata(dir).and_then(|meta| meta.modified()).ok()
+}
+
+fn scan(dir: &str) -> Vec<String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries.filter_map(Result::ok)
+        .filter(|entry| {
+            fs::metadata(entry.path())
+                .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+impl PathIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prefetch(&self, path: &str) {
+        let index = self.clone();
+        let path = path.to_owned();
+        thread::spawn(move || index.commands(&path));
+    }
+
+    pub fn commands(&self, path: &str) -> Vec<String> {
+        let dirs: Vec<_> = path.split(':').filter(|dir| !dir.is_empty()).map(|dir| (dir, mtime(dir))).collect();
+        let stale: Vec<_> = {
+            let cached = self.dirs.lock().unwrap_or_else(|error| error.into_inner());
+            dirs.iter()
+                .filter(|&&(dir, mtime)| mtime.is_none() || cached.get(dir).map(|cached| cached.mtime) != Some(mtime))
+                .cloned()
+                .collect()
+        };
+        let scanned: Vec<_> = stale.into_iter()
+            .map(|(dir, mtime)| (dir.to_owned(), Dir {
+                mtime,
+                names: scan(dir),
+            }))
+            .collect();
+        let mut cached = self.dirs.lock().unwrap_or_else(|error| error.into_inner());
+        cached.extend(scanned);
+        let mut names: Vec<_> = dirs.iter()
+            .filter_map(|&(dir, _)| cached.get(dir))
+            .flat_map(|dir| dir.names.iter().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
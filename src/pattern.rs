@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+const CACHE_SIZE: usize = 256;
+
+enum PatternToken {
+    Char(char),
+    AnyChar,
+    AnyString,
+    Class(bool, Vec<(char, char)>),
+}
+
+pub struct Pattern {
+    tokens: Vec<PatternToken>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        let chars: Vec<_> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' => tokens.push(PatternToken::AnyString),
+                '?' => tokens.push(PatternToken::AnyChar),
+                '\\' if i+1 < chars.len() => {
+                    i += 1;
+                    tokens.push(PatternToken::Char(chars[i]));
+                },
+                '[' => match Self::class(&chars[i+1 ..]) {
+                    Some((token, len)) => {
+                        tokens.push(token);
+                        i += len;
+                    },
+                    None => tokens.push(PatternToken::Char('[')),
+                },
+                c => tokens.push(PatternToken::Char(c)),
+            }
+            i += 1;
+        }
+        Self {
+            tokens,
+        }
+    }
+
+    fn class(chars: &[char]) -> Option<(PatternToken, usize)> {
+        let mut i = 0;
+        let negated = i < chars.len() && (chars[i] == '!' || chars[i] == '^');
+        if negated {
+            i += 1;
+        }
+        let mut ranges = Vec::new();
+        let start = i;
+        while i < chars.len() && (chars[i] != ']' || i == start) {
+            let lo = chars[i];
+            if i+2 < chars.len() && chars[i+1] == '-' && chars[i+2] != ']' {
+                ranges.push((lo, chars[i+2]));
+                i += 3;
+            } else {
+                ranges.push((lo, lo));
+                i += 1;
+            }
+        }
+        if i == chars.len() {
+            None
+        } else {
+            Some((PatternToken::Class(negated, ranges), i+1))
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<_> = text.chars().collect();
+        let (mut t, mut p) = (0, 0);
+        let mut backtrack = None;
+        while t < text.len() {
+            let matched = match self.tokens.get(p) {
+                Some(&PatternToken::AnyString) => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                },
+                Some(&PatternToken::AnyChar) => true,
+                Some(&PatternToken::Char(c)) => c == text[t],
+                Some(&PatternToken::Class(negated, ref ranges)) => {
+                    ranges.iter().any(|&(lo, hi)| lo <= text[t] && text[t] <= hi) != negated
+                },
+                None => false,
+            };
+            if matched {
+                p += 1;
+                t += 1;
+            } else if let Some((star, pos)) = backtrack {
+                p = star + 1;
+                t = pos + 1;
+                backtrack = Some((star, pos + 1));
+            } else {
+                return false;
+            }
+        }
+        self.tokens[p ..].iter().all(|token| matches!(*token, PatternToken::AnyString))
+    }
+}
+
+#[derive(Default)]
+pub struct PatternCache {
+    patterns: HashMap<String, Pattern>,
+}
+
+impl PatternCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, pattern: &str) -> &Pattern {
+        if !self.patterns.contains_key(pattern) {
+            if self.patterns.len() >= CACHE_SIZE {
+                self.patterns.clear();
+            }
+            self.patterns.insert(pattern.to_owned(), Pattern::new(pattern));
+        }
+        &self.patterns[pattern]
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
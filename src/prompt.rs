@@ -0,0 +1,192 @@
+use git;
+use shell::Rush;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::io::{stdout, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use sys::{geteuid, gethostname, isatty, strftime, time, username};
+use theme;
+
+const DEADLINE: Duration = Duration::from_millis(30);
+
+#[derive(Default)]
+pub struct AsyncSegment {
+    values: RefCell<HashMap<String, (String, bool)>>,
+    pending: RefCell<Option<(String, Receiver<String>)>>,
+}
+
+impl AsyncSegment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str, compute: fn(&str) -> String) -> String {
+        self.wait(Duration::from_secs(0));
+        if let Some(&(ref value, true)) = self.values.borrow().get(key) {
+            return value.clone();
+        }
+        if self.pending.borrow().is_none() {
+            let (sender, receiver) = mpsc::channel();
+            let arg = key.to_owned();
+            thread::spawn(move || {
+                let _ = sender.send(compute(&arg));
+            });
+            *self.pending.borrow_mut() = Some((key.to_owned(), receiver));
+        }
+        self.wait(DEADLINE);
+        self.values.borrow().get(key).map(|value| value.0.clone()).unwrap_or_default()
+    }
+
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let mut pending = self.pending.borrow_mut();
+        let result = match *pending {
+            Some((_, ref receiver)) => receiver.recv_timeout(timeout),
+            None => return false,
+        };
+        match result {
+            Ok(value) => {
+                let (key, _) = pending.take().unwrap();
+                self.values.borrow_mut().insert(key, (value, true));
+                true
+            },
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                *pending = None;
+                false
+            },
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.borrow().is_some()
+    }
+
+    pub fn invalidate(&self) {
+        for value in self.values.borrow_mut().values_mut() {
+            value.1 = false;
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{}m{}s", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else if secs >= 60 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{}s", secs)
+    } else {
+        format!("{}ms", duration.subsec_millis())
+    }
+}
+
+pub fn expand_prompt(prompt: &str, rush: &Rush) -> String {
+    let mut result = String::new();
+    let mut chars = prompt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let escape = match chars.next() {
+            Some(escape) => escape,
+            None => {
+                result.push('\\');
+                break;
+            },
+        };
+        match escape {
+            'a' => result.push('\x07'),
+            'e' => result.push('\x1b'),
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            '[' => result.push('\x01'),
+            ']' => result.push('\x02'),
+            '\\' => result.push('\\'),
+            '$' => result.push(if geteuid() == 0 { '#' } else { '$' }),
+            's' => result.push_str(rush.name.rsplit('/').next().unwrap_or("").trim_start_matches('-')),
+            'v' | 'V' => result.push_str(env!("CARGO_PKG_VERSION")),
+            'j' => result.push_str(&rush.jobs.len().to_string()),
+            'g' if rush.prompted => result.push_str(&rush.git_prompt.get(&rush.cwd, git::prompt)),
+            'g' => {},
+            '?' => result.push_str(&rush.status.to_string()),
+            'E' => result.push_str(&format_duration(rush.duration)),
+            'u' => result.push_str(&rush.vars.get("USER").map_or_else(username, str::to_string)),
+            'h' => result.push_str(gethostname().split('.').next().unwrap_or("")),
+            'H' => result.push_str(&gethostname()),
+            'w' | 'W' => {
+                let dir = rush.tilde(rush.cwd.clone());
+                if escape == 'w' || dir == "/" || dir == "~" {
+                    result.push_str(&dir);
+                } else {
+                    result.push_str(dir.rsplit('/').next().unwrap_or(""));
+                }
+            },
+            'd' => result.push_str(&strftime("%a %b %d", time())),
+            't' => result.push_str(&strftime("%H:%M:%S", time())),
+            'T' => result.push_str(&strftime("%I:%M:%S", time())),
+            '@' => result.push_str(&strftime("%I:%M %p", time())),
+            'A' => result.push_str(&strftime("%H:%M", time())),
+            'D' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let format: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let format = if format.is_empty() { "%X" } else { &format };
+                result.push_str(&strftime(format, time()));
+            },
+            '0' ..= '7' => {
+                let mut code = escape.to_digit(8).unwrap();
+                for _ in 0 .. 2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        },
+                        None => break,
+                    }
+                }
+                result.extend(std::char::from_u32(code));
+            },
+            _ => {
+                result.push('\\');
+                result.push(escape);
+            },
+        }
+    }
+    result
+}
+
+impl Rush {
+    pub fn prompt(&self) -> String {
+        if let Some(ps1) = self.vars.get("PS1") {
+            return expand_prompt(ps1, self);
+        }
+        match self.vars.get("RUSH_THEME").and_then(theme::find) {
+            Some(theme) => theme.render(|template| expand_prompt(template, self), self.status, self.use_color(1)),
+            None => "$ ".to_string(),
+        }
+    }
+
+    pub fn rprompt(&self) -> String {
+        self.vars.get("RPROMPT").map(|rprompt| expand_prompt(rprompt, self)).unwrap_or_default()
+    }
+
+    pub fn report_cwd(&self) {
+        if !self.interactive || !isatty(1) {
+            return;
+        }
+        let mut path = String::new();
+        for b in self.cwd.bytes() {
+            if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+                path.push(b as char);
+            } else {
+                path.push_str(&format!("%{:02X}", b));
+            }
+        }
+        print!("\x1b]7;file://{}{}\x1b\\", gethostname(), path);
+        let _ = stdout().flush();
+    }
+}
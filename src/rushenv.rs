@@ -0,0 +1,138 @@
+use dirs::normalize;
+use expand::expand;
+use history::replace_private;
+use lexer::is_name;
+use shell::Rush;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub struct DirEnv {
+    pub dir: String,
+    saved: Vec<(String, Option<String>, Option<OsString>)>,
+}
+
+fn find_rushenv(cwd: &str) -> Option<String> {
+    let mut dir = Path::new(cwd);
+    loop {
+        if dir.join(".rushenv").is_file() {
+            return dir.to_str().map(str::to_string);
+        }
+        dir = dir.parent()?;
+    }
+}
+
+impl Rush {
+    fn allowfile(&self) -> Option<String> {
+        self.data_file("rushenv_allow", None)
+    }
+
+    pub fn allowed_envs(&self) -> Vec<String> {
+        let text = self.allowfile().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect()
+    }
+
+    pub fn allow_env(&mut self, dir: &str, allow: bool) -> io::Result<()> {
+        let dir = normalize(&if dir.starts_with('/') { dir.to_owned() } else { format!("{}/{}", self.cwd, dir) });
+        let path = self.allowfile().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+        let mut dirs = self.allowed_envs();
+        dirs.retain(|allowed| *allowed != dir);
+        if allow {
+            dirs.push(dir);
+        }
+        replace_private(&path, |mut file| {
+            for dir in dirs {
+                writeln!(file, "{}", dir)?;
+            }
+            Ok(())
+        })?;
+        self.update_dir_env(true);
+        Ok(())
+    }
+
+    fn unload_dir_env(&mut self) {
+        let dir_env = match self.dir_env.take() {
+            Some(dir_env) => dir_env,
+            None => return,
+        };
+        for (name, value, exported) in dir_env.saved.into_iter().rev() {
+            match value {
+                Some(value) => self.vars.vars.insert(name.clone(), value),
+                None => self.vars.vars.remove(&name),
+            };
+            match exported {
+                Some(exported) => env::set_var(&name, exported),
+                None => env::remove_var(&name),
+            }
+        }
+    }
+
+    fn load_dir_env(&mut self, dir: String) {
+        let path = format!("{}/.rushenv", dir);
+        let mut dir_env = DirEnv {
+            dir,
+            saved: Vec::new(),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("rush: {}: {}", path, error);
+                self.dir_env = Some(dir_env);
+                return;
+            },
+        };
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let assignment = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            let (name, value) = match assignment.split_once('=') {
+                Some((name, value)) if is_name(name) => (name, value),
+                _ => {
+                    eprintln!("rush: {}: line {}: invalid assignment", path, lineno+1);
+                    continue;
+                },
+            };
+            let value = match expand(value, self) {
+                Ok(value) => value,
+                Err(error) => {
+                    eprintln!("rush: {}: line {}: {}", path, lineno+1, error);
+                    continue;
+                },
+            };
+            if !dir_env.saved.iter().any(|saved| saved.0 == name) {
+                dir_env.saved.push((name.to_owned(), self.vars.get(name).map(str::to_string), env::var_os(name)));
+            }
+            env::set_var(name, &value);
+            self.vars.set(name, &value);
+        }
+        self.dir_env = Some(dir_env);
+    }
+
+    pub fn update_dir_env(&mut self, force: bool) {
+        if self.restricted {
+            return;
+        }
+        let found = find_rushenv(&self.cwd);
+        if !force && found == self.dir_env.as_ref().map(|dir_env| dir_env.dir.clone()) {
+            return;
+        }
+        self.unload_dir_env();
+        let dir = match found {
+            Some(dir) => dir,
+            None => return,
+        };
+        if !self.allowed_envs().contains(&dir) {
+            eprintln!("rush: {}/.rushenv is not allowed; run `rushenv allow` to trust it", dir);
+            self.dir_env = Some(DirEnv {
+                dir,
+                saved: Vec::new(),
+            });
+            return;
+        }
+        self.load_dir_env(dir);
+    }
+}
@@ -0,0 +1,50 @@
+use history::replace_private;
+use jobs::State;
+use shell::Rush;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+impl Rush {
+    pub fn session_file(&self) -> Option<String> {
+        self.data_file("session", None)
+    }
+
+    pub fn save_session(&self) -> io::Result<()> {
+        let path = match self.session_file() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        replace_private(&path, |mut file| {
+            writeln!(file, "status {}", self.status)?;
+            let dirs = Some(("cwd", &self.cwd)).into_iter().chain(self.dirs.iter().map(|dir| ("dir", dir)));
+            for (kind, dir) in dirs.filter(|(_, dir)| !dir.contains('\n')) {
+                writeln!(file, "{} {}", kind, dir)?;
+            }
+            for job in self.jobs.iter().filter(|job| !matches!(job.state, State::Done(_))) {
+                writeln!(file, "job {}", job.cmd)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn restore_session(&mut self) {
+        let text = match self.session_file().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return,
+        };
+        let mut dirs = Vec::new();
+        for line in text.lines() {
+            match line.split_once(' ') {
+                Some(("status", status)) => self.status = status.parse().unwrap_or(0),
+                Some(("cwd", cwd)) if Path::new(cwd).is_dir() => {
+                    self.chdir(cwd, false);
+                },
+                Some(("dir", dir)) if Path::new(dir).is_dir() => dirs.push(dir.to_owned()),
+                Some(("job", cmd)) => eprintln!("rush: job running at last exit: {}", cmd),
+                _ => {},
+            }
+        }
+        self.dirs = dirs;
+    }
+}
@@ -0,0 +1,692 @@
+use bash;
+use builtins::{Builtin, BUILTINS};
+use complete::{self, Matcher, Spec};
+use dirs::normalize;
+use editor::{Complete, Editor};
+use error::ShellError;
+use expand::expand;
+use frecency;
+use highlight::{self, Style};
+use history::History;
+use jobs::Jobs;
+use keymap::Keymap;
+use lexer::split_words;
+use pathindex::PathIndex;
+use pattern::PatternCache;
+use libc::{self, c_int};
+use prompt::AsyncSegment;
+use rushenv::DirEnv;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, stdin, stdout, BufRead, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use sys::{exit, getcwd, isatty, same_file, tcgetattr, tcsetattr, time, Libc};
+
+pub const OPTIONS: &[(&str, Option<char>)] = &[
+    ("autopushd", None),
+    ("autosuggest", None),
+    ("bashcompat", None),
+    ("cdspell", None),
+    ("correct", None),
+    ("emacs", None),
+    ("errexit", Some('e')),
+    ("fuzzycomplete", None),
+    ("highlight", None),
+    ("histexpand", Some('H')),
+    ("history", None),
+    ("nocasecomplete", None),
+    ("nounset", Some('u')),
+    ("posix", None),
+    ("private", None),
+    ("session", None),
+    ("sharehistory", None),
+    ("vi", None),
+    ("xtrace", Some('x')),
+];
+
+pub const HOOKS: &[&str] = &["on_startup", "on_prompt", "on_command", "on_chdir", "on_job_done", "on_command_not_found",
+                             "on_exit"];
+
+pub struct Options {
+    pub autopushd: bool,
+    autosuggest: bool,
+    pub bashcompat: bool,
+    pub cdspell: bool,
+    pub correct: bool,
+    emacs: bool,
+    pub errexit: bool,
+    fuzzycomplete: bool,
+    highlight: bool,
+    histexpand: bool,
+    pub history: bool,
+    nocasecomplete: bool,
+    pub nounset: bool,
+    pub posix: bool,
+    pub private: bool,
+    pub session: bool,
+    pub sharehistory: bool,
+    vi: bool,
+    pub xtrace: bool,
+}
+
+impl Options {
+    fn new() -> Self {
+        Self {
+            autopushd: false,
+            autosuggest: true,
+            bashcompat: false,
+            cdspell: false,
+            correct: false,
+            emacs: true,
+            errexit: false,
+            fuzzycomplete: false,
+            highlight: true,
+            histexpand: false,
+            history: false,
+            nocasecomplete: false,
+            nounset: false,
+            posix: false,
+            private: false,
+            session: false,
+            sharehistory: false,
+            vi: false,
+            xtrace: false,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "autopushd" => Some(self.autopushd),
+            "autosuggest" => Some(self.autosuggest),
+            "bashcompat" => Some(self.bashcompat),
+            "cdspell" => Some(self.cdspell),
+            "correct" => Some(self.correct),
+            "emacs" => Some(self.emacs),
+            "errexit" => Some(self.errexit),
+            "fuzzycomplete" => Some(self.fuzzycomplete),
+            "highlight" => Some(self.highlight),
+            "histexpand" => Some(self.histexpand),
+            "history" => Some(self.history),
+            "nocasecomplete" => Some(self.nocasecomplete),
+            "nounset" => Some(self.nounset),
+            "posix" => Some(self.posix),
+            "private" => Some(self.private),
+            "session" => Some(self.session),
+            "sharehistory" => Some(self.sharehistory),
+            "vi" => Some(self.vi),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "autopushd" => Some(&mut self.autopushd),
+            "autosuggest" => Some(&mut self.autosuggest),
+            "bashcompat" => Some(&mut self.bashcompat),
+            "cdspell" => Some(&mut self.cdspell),
+            "correct" => Some(&mut self.correct),
+            "emacs" => Some(&mut self.emacs),
+            "errexit" => Some(&mut self.errexit),
+            "fuzzycomplete" => Some(&mut self.fuzzycomplete),
+            "highlight" => Some(&mut self.highlight),
+            "histexpand" => Some(&mut self.histexpand),
+            "history" => Some(&mut self.history),
+            "nocasecomplete" => Some(&mut self.nocasecomplete),
+            "nounset" => Some(&mut self.nounset),
+            "posix" => Some(&mut self.posix),
+            "private" => Some(&mut self.private),
+            "session" => Some(&mut self.session),
+            "sharehistory" => Some(&mut self.sharehistory),
+            "vi" => Some(&mut self.vi),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        match self.get_mut(name) {
+            Some(value) => *value = on,
+            None => return false,
+        }
+        match name {
+            "emacs" if on => self.vi = false,
+            "vi" if on => self.emacs = false,
+            _ => {},
+        }
+        true
+    }
+
+    pub fn by_letter(letter: char) -> Option<&'static str> {
+        OPTIONS.iter().find(|opt| opt.1 == Some(letter)).map(|opt| opt.0)
+    }
+}
+
+pub struct Vars {
+    pub vars: HashMap<String, String>,
+}
+
+impl Vars {
+    fn new() -> Self {
+        Self {
+            vars: env::vars().collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(|s| s.as_str())
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        if env::var_os(name).is_some() {
+            env::set_var(name, value);
+        }
+        self.vars.insert(name.to_owned(), value.to_owned());
+    }
+}
+
+pub struct Rush {
+    pub name: String,
+    pub history: History,
+    pub jobs: Jobs,
+    pub vars: Vars,
+    pub options: Options,
+    pub status: c_int,
+    pub interactive: bool,
+    pub history_replaced: bool,
+    editor: Option<Editor>,
+    pub completions: HashMap<String, Spec>,
+    pub builtins: HashMap<&'static str, &'static dyn Builtin>,
+    pub disabled_builtins: HashMap<&'static str, &'static dyn Builtin>,
+    pub duration: Duration,
+    pub dirs: Vec<String>,
+    pub cwd: String,
+    pub named_dirs: HashMap<String, String>,
+    pub hooks: HashMap<&'static str, Vec<String>>,
+    pub aliases: HashMap<String, String>,
+    pub keymap: Keymap,
+    pub path_index: PathIndex,
+    pub patterns: PatternCache,
+    pub git_prompt: AsyncSegment,
+    pub prompted: bool,
+    pub startup: Vec<(&'static str, Instant)>,
+    pub startup_profile: bool,
+    pub login: bool,
+    pub restricted: bool,
+    pub dir_env: Option<DirEnv>,
+    pub dirfile_failed: bool,
+    termios: Option<libc::termios>,
+    in_hook: bool,
+}
+
+impl Rush {
+    pub fn new() -> Self {
+        let start = Instant::now();
+        let mut rush = Self {
+            name: "rush".to_owned(),
+            history: History::new(),
+            jobs: Jobs::new(),
+            vars: Vars::new(),
+            options: Options::new(),
+            status: 0,
+            interactive: isatty(0),
+            history_replaced: false,
+            editor: None,
+            completions: HashMap::new(),
+            builtins: HashMap::new(),
+            disabled_builtins: HashMap::new(),
+            duration: Duration::from_secs(0),
+            dirs: Vec::new(),
+            cwd: getcwd(),
+            named_dirs: HashMap::new(),
+            hooks: HashMap::new(),
+            aliases: HashMap::new(),
+            keymap: Keymap::new(),
+            path_index: PathIndex::new(),
+            patterns: PatternCache::new(),
+            git_prompt: AsyncSegment::new(),
+            prompted: false,
+            startup: vec![("start", start)],
+            startup_profile: false,
+            login: false,
+            restricted: false,
+            dir_env: None,
+            dirfile_failed: false,
+            termios: None,
+            in_hook: false,
+        };
+        if let Some(pwd) = rush.vars.get("PWD").map(str::to_string) {
+            if pwd.starts_with('/') && normalize(&pwd) == pwd && same_file(&pwd, ".") {
+                rush.cwd = pwd;
+            }
+        }
+        let cwd = rush.cwd.clone();
+        rush.vars.set("PWD", &cwd);
+        rush.mark("vars");
+        for &builtin in BUILTINS {
+            rush.register(builtin);
+        }
+        rush.mark("builtins");
+        if rush.interactive {
+            rush.termios = tcgetattr(0);
+            rush.editor = Some(Editor::new());
+            rush.load_inputrc();
+            rush.mark("inputrc");
+        }
+        rush.options.histexpand = rush.interactive;
+        rush.options.history = rush.interactive;
+        rush.load_config();
+        rush.mark("config");
+        if rush.options.history {
+            rush.load_history();
+            rush.mark("history");
+        }
+        if rush.interactive && rush.options.session {
+            rush.restore_session();
+            rush.mark("session");
+        }
+        rush
+    }
+
+    fn mark(&mut self, stage: &'static str) {
+        if !self.prompted {
+            self.startup.push((stage, Instant::now()));
+        }
+    }
+
+    fn report_startup(&self) {
+        let start = self.startup[0].1;
+        let mut last = start;
+        for &(stage, time) in &self.startup[1 ..] {
+            eprintln!("startup: {:<10} {:>8.3}ms", stage, (time - last).as_secs_f64() * 1000.0);
+            last = time;
+        }
+        eprintln!("startup: {:<10} {:>8.3}ms", "total", (last - start).as_secs_f64() * 1000.0);
+    }
+
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if let Some(mut editor) = self.editor.take() {
+            let result = {
+                let history: Vec<_> = self.history.iter().map(|(_, entry)| entry.line.as_str()).collect();
+                editor.set_vi(self.options.vi);
+                let colors = self.use_color(1);
+                editor.set_autosuggest(self.options.autosuggest && colors);
+                editor.set_highlight(self.options.highlight && colors);
+                editor.set_keymap(&self.keymap);
+                editor.read_line(prompt, &history, self)
+            };
+            self.editor = Some(editor);
+            return result;
+        }
+        if !self.options.posix || self.interactive {
+            print!("{}", prompt.replace(['\x01', '\x02'], ""));
+            stdout().flush()?;
+        }
+        let mut line = Vec::new();
+        if stdin().lock().read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+    }
+
+    pub fn register(&mut self, builtin: &'static dyn Builtin) {
+        self.builtins.insert(builtin.name(), builtin);
+    }
+
+    fn builtin_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.builtins.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn path_commands(&self) -> Vec<String> {
+        self.path_index.commands(self.vars.get("PATH").unwrap_or(""))
+    }
+
+    pub fn suggest_commands(&self, name: &str) -> Vec<String> {
+        let limit = (name.chars().count() / 3).max(1);
+        let mut candidates: Vec<_> = self.builtin_names().into_iter().map(str::to_string)
+            .chain(self.aliases.keys().cloned())
+            .chain(self.path_commands())
+            .map(|candidate| (complete::distance(name, &candidate), candidate))
+            .filter(|&(distance, _)| distance <= limit)
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        let best = candidates.first().map_or(0, |candidate| candidate.0);
+        candidates.into_iter().take_while(|candidate| candidate.0 == best).take(3).map(|candidate| candidate.1).collect()
+    }
+
+    pub fn compgen(&self, spec: &Spec, word: &str, words: &[String], cword: usize) -> Vec<String> {
+        let matcher = self.matcher();
+        let mut candidates = Vec::new();
+        if let Some(ref list) = spec.words {
+            candidates.extend(complete::words(matcher, word, list.split_whitespace().map(str::to_string)));
+        }
+        if spec.files {
+            candidates.extend(complete::filenames(matcher, word));
+        } else if spec.dirs {
+            candidates.extend(complete::directories(matcher, word));
+        }
+        if spec.commands {
+            candidates.extend(complete::commands(matcher, word, &self.builtin_names(), &self.path_commands()));
+        } else if spec.builtins {
+            candidates.extend(complete::words(matcher, word, self.builtin_names().iter().map(|name| name.to_string())));
+        }
+        if spec.vars {
+            candidates.extend(complete::words(matcher, word, self.vars.vars.keys().cloned()));
+        }
+        if let Some(ref command) = spec.command {
+            let argv: Vec<_> = split_words(command).iter().map(|arg| complete::unescape(arg)).collect();
+            let prev = if cword > 0 { words[cword-1].as_str() } else { "" };
+            let output = argv.first().and_then(|prog| {
+                Command::new(prog)
+                    .args(&argv[1 ..])
+                    .args([words[0].as_str(), word, prev])
+                    .env("COMP_LINE", words.join(" "))
+                    .env("COMP_WORDS", words.iter().map(|word| complete::escape(word)).collect::<Vec<_>>().join(" "))
+                    .env("COMP_CWORD", cword.to_string())
+                    .stderr(Stdio::null())
+                    .output()
+                    .ok()
+            });
+            if let Some(output) = output {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                candidates.extend(stdout.lines().filter(|line| !line.is_empty()).map(complete::escape));
+            }
+        }
+        if let (Some(ref function), Some(ref script)) = (&spec.function, &spec.script) {
+            let lib = self.vars.get("BASH_COMPLETION").unwrap_or(complete::BASH_COMPLETION);
+            candidates.extend(complete::bash_function(lib, script, function, words, cword));
+        }
+        if candidates.is_empty() && spec.default {
+            candidates.extend(complete::filenames(matcher, word));
+        }
+        let mut seen = HashSet::new();
+        candidates.retain(|candidate| seen.insert(candidate.clone()));
+        candidates
+    }
+
+    fn matcher(&self) -> Matcher {
+        Matcher {
+            fuzzy: self.options.fuzzycomplete,
+            nocase: self.options.nocasecomplete,
+        }
+    }
+
+    pub fn run(&mut self) {
+        if !self.interactive {
+            self.source_env();
+        }
+        self.update_dir_env(false);
+        self.report_cwd();
+        self.run_hooks("on_startup");
+        self.mark("on_startup");
+        loop {
+            if self.options.history && self.options.sharehistory {
+                self.sync_history();
+            }
+            self.reap_jobs();
+            self.run_hooks("on_prompt");
+            self.git_prompt.invalidate();
+            let prompt = self.prompt();
+            let rprompt = self.rprompt();
+            if let Some(ref mut editor) = self.editor {
+                editor.set_rprompt(rprompt);
+            }
+            if !self.prompted {
+                self.mark("prompt");
+                if self.startup_profile {
+                    self.report_startup();
+                }
+                if self.interactive {
+                    self.path_index.prefetch(self.vars.get("PATH").unwrap_or(""));
+                }
+                self.prompted = true;
+            }
+            let mut input = match self.read_line(&prompt) {
+                Ok(Some(input)) => input,
+                Ok(None) => self.exit(0),
+                Err(error) => {
+                    self.report(&ShellError::Io("stdin".to_owned(), error));
+                    self.exit(1);
+                },
+            };
+            if input.find('\0').is_some() {
+                self.report(&ShellError::Parse("nul byte found in the input".to_owned()));
+                continue;
+            }
+            if input.ends_with('\n') {
+                input.pop();
+            }
+            for line in input.split('\n') {
+                self.run_line(line.to_string());
+            }
+        }
+    }
+
+    pub fn run_line(&mut self, line: String) {
+        let mut line = line;
+        if self.options.histexpand {
+            match self.history.expand(&line) {
+                Ok(Some(expanded)) => {
+                    println!("{}", expanded);
+                    line = expanded;
+                },
+                Ok(None) => {},
+                Err(error) => {
+                    self.report(&ShellError::History(error));
+                    return;
+                },
+            }
+        }
+        self.reap_jobs();
+        if !line.trim().is_empty() {
+            self.vars.set("RUSH_COMMAND", &line);
+            self.run_hooks("on_command");
+        }
+        let start = Instant::now();
+        self.execute(&line);
+        self.duration = start.elapsed();
+        if !self.history_replaced {
+            self.add_history(line);
+        }
+        self.history_replaced = false;
+    }
+
+    pub fn run_hooks(&mut self, hook: &str) {
+        if self.in_hook {
+            return;
+        }
+        let mut cmds = self.hooks.get(hook).cloned().unwrap_or_default();
+        if hook == "on_command" {
+            cmds.extend(self.vars.get("PREEXEC_COMMAND").map(str::to_string));
+        }
+        let (status, errexit) = (self.status, self.options.errexit);
+        self.options.errexit = false;
+        self.in_hook = true;
+        for cmd in cmds.iter().filter(|cmd| !cmd.trim().is_empty()) {
+            self.execute(cmd);
+        }
+        self.in_hook = false;
+        self.status = status;
+        self.options.errexit = errexit;
+    }
+
+    fn reap_jobs(&mut self) {
+        self.jobs.update(&mut Libc);
+        for job in self.jobs.reap() {
+            self.vars.set("RUSH_JOB", &job.cmd);
+            self.run_hooks("on_job_done");
+        }
+    }
+
+    pub fn sanitize_terminal(&mut self, status: c_int) {
+        if !self.interactive {
+            return;
+        }
+        if !libc::WIFSIGNALED(status) && !libc::WIFSTOPPED(status) {
+            self.termios = tcgetattr(0).or(self.termios);
+            return;
+        }
+        if let Some(ref saved) = self.termios {
+            tcsetattr(0, saved);
+        }
+        if isatty(1) {
+            print!("\x1b[?1049l");
+            let _ = stdout().flush();
+        }
+    }
+
+    pub fn exit(&mut self, status: c_int) -> ! {
+        if self.interactive && self.options.session {
+            if let Err(error) = self.save_session() {
+                eprintln!("rush: session: {}", error);
+            }
+        }
+        if self.login {
+            self.login = false;
+            self.run_logout();
+        }
+        self.run_hooks("on_exit");
+        exit(status)
+    }
+
+    fn source_env(&mut self) {
+        let path = match self.vars.get("ENV").map(|env| expand(env, self)) {
+            Some(Ok(path)) if !path.is_empty() => path,
+            Some(Err(error)) => {
+                eprintln!("rush: ENV: {}", error);
+                return;
+            },
+            _ => return,
+        };
+        if let Err(error) = self.source_file("rush", &path) {
+            eprintln!("rush: {}: {}", path, error);
+        }
+    }
+
+    fn run_logout(&mut self) {
+        if let Some(path) = self.config_file("logout", Some(".rush_logout")) {
+            let _ = self.source_file("rush", &path);
+        }
+    }
+
+    pub fn source_file(&mut self, name: &str, path: &str) -> io::Result<c_int> {
+        let script = fs::read_to_string(path)?;
+        self.status = 0;
+        if self.options.bashcompat {
+            for command in bash::commands(&script) {
+                match command {
+                    Ok(command) => self.execute(command),
+                    Err(lineno) => eprintln!("{}: {}:{}: skipping unsupported construct", name, path, lineno),
+                }
+            }
+        } else {
+            for line in script.lines() {
+                self.execute(line);
+            }
+        }
+        Ok(self.status)
+    }
+}
+
+impl Default for Rush {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Complete for Rush {
+    fn complete(&self, line: &[char], pos: usize) -> (usize, Vec<String>) {
+        let matcher = self.matcher();
+        let start = complete::word_start(line, pos);
+        if let Some((dollar, name, braced)) = complete::variable(&line[start .. pos]) {
+            let names = complete::words(matcher, &name, self.vars.vars.keys().cloned());
+            let candidates = names.iter()
+                .map(|name| if braced { format!("${{{}}}", name) } else { format!("${}", name) })
+                .collect();
+            return (start + dollar, candidates);
+        }
+        let word = complete::unescape(&line[start .. pos].iter().collect::<String>());
+        let prev_words = complete::command_words(line, start);
+        if prev_words.is_empty() {
+            if word.contains('/') {
+                return (start, complete::filenames(matcher, &word));
+            }
+            return (start, complete::commands(matcher, &word, &self.builtin_names(), &self.path_commands()));
+        }
+        let name = prev_words.first().cloned().unwrap_or_default();
+        let prev = line[.. start].iter().collect::<String>();
+        let prev = prev.split_whitespace().last().unwrap_or("");
+        if let Some(spec) = self.completions.get(&name) {
+            let mut words = prev_words.clone();
+            let cword = words.len();
+            words.push(word.clone());
+            return (start, self.compgen(spec, &word, &words, cword));
+        }
+        let candidates = match name.as_str() {
+            "cd" => complete::directories(matcher, &word),
+            "j" => match self.dirfile() {
+                Some(file) => {
+                    let mut words: Vec<_> = prev_words.iter().skip(1).cloned().collect();
+                    words.push(word.clone());
+                    frecency::search(&file, &words, time()).into_iter()
+                        .map(|entry| complete::escape(&entry.path))
+                        .collect()
+                },
+                None => Vec::new(),
+            },
+            "kill" | "fg" | "bg" => {
+                let mut specs: Vec<_> = self.jobs.pids().map(|pid| pid.to_string()).collect();
+                for job in self.jobs.iter() {
+                    specs.push(format!("%{}", job.id));
+                    specs.extend(job.cmd.split_whitespace().next().map(|name| format!("%{}", name)));
+                }
+                complete::words(matcher, &word, specs)
+            },
+            "ssh" | "sftp" => complete::hostnames(matcher, &word, ""),
+            "scp" | "rsync" if !word.contains(':') && !word.contains('/') => {
+                let mut candidates = complete::hostnames(matcher, &word, ":");
+                candidates.extend(complete::filenames(matcher, &word));
+                candidates
+            },
+            "unset" | "export" => complete::words(matcher, &word, self.vars.vars.keys().cloned()),
+            "set" if prev == "-o" || prev == "+o" => {
+                complete::words(matcher, &word, OPTIONS.iter().map(|opt| opt.0.to_string()))
+            },
+            _ => complete::filenames(matcher, &word),
+        };
+        (start, candidates)
+    }
+
+    fn prompt_pending(&self) -> bool {
+        self.git_prompt.is_pending()
+    }
+
+    fn highlight(&self, line: &str) -> Vec<Style> {
+        highlight::highlight(line, self)
+    }
+
+    fn poll_prompt(&self) -> Option<(String, String)> {
+        if self.git_prompt.wait(Duration::from_secs(0)) {
+            Some((self.prompt(), self.rprompt()))
+        } else {
+            None
+        }
+    }
+
+    fn external_editor(&self) -> Option<String> {
+        if self.restricted {
+            return None;
+        }
+        Some(self.vars.get("VISUAL").or_else(|| self.vars.get("EDITOR")).unwrap_or("vi").to_owned())
+    }
+
+    fn search_history(&self, query: &str, before: usize) -> Option<(usize, String)> {
+        let path = self.histfile()?;
+        self.history.search_file(&path, query, before).ok()?
+    }
+}
@@ -0,0 +1,618 @@
+use libc::{self, c_char, c_int, pid_t};
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::ffi::{CString, CStr};
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Read};
+use std::mem;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, ExitStatus, Stdio};
+use std::ptr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+pub fn retry<T: Copy + PartialEq + From<i8>, F: FnMut() -> T>(mut call: F) -> T {
+    loop {
+        let ret = call();
+        if ret != T::from(-1) || errno() != libc::EINTR {
+            return ret;
+        }
+    }
+}
+
+fn cstring(s: &str) -> Option<CString> {
+    let s = CString::new(s).ok();
+    if s.is_none() {
+        set_errno(libc::EINVAL);
+    }
+    s
+}
+
+pub fn chdir(dir: &str) -> c_int {
+    let dir = match cstring(dir) {
+        Some(dir) => dir,
+        None => return -1,
+    };
+    unsafe {
+        libc::chdir(dir.as_ptr())
+    }
+}
+
+pub fn close(fd: c_int) -> c_int {
+    unsafe {
+        libc::close(fd)
+    }
+}
+
+pub fn dup2(src: c_int, dst: c_int) -> c_int {
+    if src == dst {
+        let ret = unsafe {
+            libc::fcntl(dst, libc::F_SETFD, 0)
+        };
+        return if ret == -1 { -1 } else { dst };
+    }
+    retry(|| unsafe {
+        libc::dup2(src, dst)
+    })
+}
+
+pub fn execvp(cmd: &[String]) -> c_int {
+    if cmd.is_empty() {
+        set_errno(libc::ENOENT);
+        return -1;
+    }
+    let prog: Vec<_> = match cmd.iter().map(|s| cstring(s)).collect() {
+        Some(prog) => prog,
+        None => return -1,
+    };
+    let mut prog: Vec<_> = prog.iter().map(|s| s.as_ptr()).collect();
+    prog.push(ptr::null());
+    unsafe {
+        libc::execvp(prog[0], prog.as_ptr())
+    }
+}
+
+extern "C" {
+    static environ: *const *mut c_char;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Redirect<'a> {
+    Fd(c_int),
+    Read(&'a str),
+    Write(&'a str),
+}
+
+unsafe fn add_redirect(actions: &mut libc::posix_spawn_file_actions_t, redirect: Redirect, fd: c_int) -> bool {
+    match redirect {
+        Redirect::Fd(src) if src == fd => {},
+        Redirect::Fd(src) => {
+            libc::posix_spawn_file_actions_adddup2(actions, src, fd);
+        },
+        Redirect::Read(path) | Redirect::Write(path) => {
+            let path = match cstring(path) {
+                Some(path) => path,
+                None => return false,
+            };
+            let flags = match redirect {
+                Redirect::Read(_) => libc::O_RDONLY,
+                _ => libc::O_WRONLY | libc::O_TRUNC | libc::O_CREAT,
+            };
+            libc::posix_spawn_file_actions_addopen(actions, fd, path.as_ptr(), flags, 0o644);
+        },
+    }
+    true
+}
+
+pub fn spawn(cmd: &[String], stdin: Redirect, stdout: Redirect) -> pid_t {
+    if cmd.is_empty() {
+        set_errno(libc::ENOENT);
+        return -1;
+    }
+    let args: Vec<_> = match cmd.iter().map(|s| cstring(s)).collect() {
+        Some(args) => args,
+        None => return -1,
+    };
+    let mut argv: Vec<_> = args.iter().map(|s| s.as_ptr() as *mut c_char).collect();
+    argv.push(ptr::null_mut());
+    let mut pid = 0;
+    let ret = unsafe {
+        let mut actions = mem::zeroed();
+        libc::posix_spawn_file_actions_init(&mut actions);
+        if !add_redirect(&mut actions, stdin, 0) || !add_redirect(&mut actions, stdout, 1) {
+            libc::posix_spawn_file_actions_destroy(&mut actions);
+            return -1;
+        }
+        let mut attr = mem::zeroed();
+        libc::posix_spawnattr_init(&mut attr);
+        let mut sigdefault = mem::zeroed();
+        libc::sigemptyset(&mut sigdefault);
+        libc::sigaddset(&mut sigdefault, libc::SIGPIPE);
+        libc::posix_spawnattr_setsigdefault(&mut attr, &sigdefault);
+        libc::posix_spawnattr_setflags(&mut attr, libc::POSIX_SPAWN_SETSIGDEF as libc::c_short);
+        let ret = libc::posix_spawnp(&mut pid, argv[0], &actions, &attr, argv.as_ptr(), environ);
+        libc::posix_spawnattr_destroy(&mut attr);
+        libc::posix_spawn_file_actions_destroy(&mut actions);
+        ret
+    };
+    if ret != 0 {
+        set_errno(ret);
+        return -1;
+    }
+    pid
+}
+
+pub fn exit(status: c_int) -> ! {
+    unsafe {
+        libc::exit(status)
+    }
+}
+
+pub fn _exit(status: c_int) -> ! {
+    unsafe {
+        libc::_exit(status)
+    }
+}
+
+pub fn fork() -> pid_t {
+    unsafe {
+        libc::fork()
+    }
+}
+
+fn flock(fd: c_int, operation: c_int) -> c_int {
+    retry(|| unsafe {
+        libc::flock(fd, operation)
+    })
+}
+
+pub fn lock(file: &File, operation: c_int) -> io::Result<()> {
+    if flock(file.as_raw_fd(), operation) == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn create_temp(dir: &Path, prefix: &str, suffix: &str) -> io::Result<(PathBuf, File)> {
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(process::id());
+        let path = dir.join(format!("{}{:016x}{}", prefix, hasher.finish(), suffix));
+        match OpenOptions::new().read(true).write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(ref error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+pub fn output_timeout(command: &mut Command, timeout: Duration) -> Option<(ExitStatus, Vec<u8>)> {
+    let mut child = command.process_group(0).stdout(Stdio::piped()).spawn().ok()?;
+    let mut stdout = child.stdout.take()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = sender.send(stdout.read_to_end(&mut output).map(|_| output));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(output) => Some((child.wait().ok()?, output.ok()?)),
+        Err(_) => {
+            unsafe {
+                libc::kill(-(child.id() as pid_t), libc::SIGKILL);
+            }
+            let _ = child.wait();
+            None
+        },
+    }
+}
+
+pub fn getcwd() -> String {
+    unsafe {
+        let cwd = libc::getcwd(ptr::null_mut(), 0);
+        if cwd.is_null() {
+            return String::new();
+        }
+        let dir = CStr::from_ptr(cwd).to_string_lossy().into_owned();
+        libc::free(cwd as *mut libc::c_void);
+        dir
+    }
+}
+
+pub fn same_file(a: &str, b: &str) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+pub fn geteuid() -> libc::uid_t {
+    unsafe {
+        libc::geteuid()
+    }
+}
+
+pub fn gethostname() -> String {
+    let mut buf = [0u8; 256];
+    unsafe {
+        libc::gethostname(buf.as_mut_ptr() as *mut c_char, buf.len());
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[.. len]).into_owned()
+}
+
+pub fn username() -> String {
+    unsafe {
+        let pw = libc::getpwuid(libc::geteuid());
+        if pw.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr((*pw).pw_name).to_string_lossy().into_owned()
+    }
+}
+
+pub fn home_dir(user: &str) -> Option<String> {
+    let user = CString::new(user).ok()?;
+    unsafe {
+        let pw = libc::getpwnam(user.as_ptr());
+        if pw.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr((*pw).pw_dir).to_string_lossy().into_owned())
+    }
+}
+
+pub fn getpgid(pid: pid_t) -> pid_t {
+    unsafe {
+        libc::getpgid(pid)
+    }
+}
+
+pub fn kill(pid: pid_t) -> c_int {
+    unsafe {
+        libc::kill(pid, libc::SIGTERM)
+    }
+}
+
+pub fn openr(path: &str) -> c_int {
+    let path = match cstring(path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    retry(|| unsafe {
+        libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC)
+    })
+}
+
+pub fn openw(path: &str) -> c_int {
+    let path = match cstring(path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    retry(|| unsafe {
+        libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_TRUNC | libc::O_CREAT | libc::O_CLOEXEC, 0o644)
+    })
+}
+
+pub fn errno() -> c_int {
+    unsafe {
+        *libc::__errno_location()
+    }
+}
+
+pub fn set_errno(errno: c_int) {
+    unsafe {
+        *libc::__errno_location() = errno;
+    }
+}
+
+pub fn perror(s: &str) {
+    unsafe {
+        let errno = *libc::__errno_location();
+        let errnostr = libc::strerror(errno);
+        let errnostr = CStr::from_ptr(errnostr).to_string_lossy();
+        eprintln!("{}: {} (errno {})", s, errnostr, errno);
+    }
+}
+
+pub fn pipe(fds: &mut [c_int; 2]) -> c_int {
+    unsafe {
+        libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC)
+    }
+}
+
+pub fn isatty(fd: c_int) -> bool {
+    unsafe {
+        libc::isatty(fd) == 1
+    }
+}
+
+pub fn tcgetattr(fd: c_int) -> Option<libc::termios> {
+    unsafe {
+        let mut termios = mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) == -1 {
+            return None;
+        }
+        Some(termios)
+    }
+}
+
+pub fn tcsetattr(fd: c_int, termios: &libc::termios) -> bool {
+    retry(|| unsafe {
+        libc::tcsetattr(fd, libc::TCSADRAIN, termios)
+    }) != -1
+}
+
+pub fn strftime(format: &str, time: i64) -> String {
+    let format = match CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    let mut buf = [0u8; 256];
+    let len = unsafe {
+        let mut tm = mem::zeroed();
+        libc::localtime_r(&(time as libc::time_t), &mut tm);
+        libc::strftime(buf.as_mut_ptr() as *mut c_char, buf.len(), format.as_ptr(), &tm)
+    };
+    String::from_utf8_lossy(&buf[.. len]).into_owned()
+}
+
+pub fn time() -> i64 {
+    unsafe {
+        libc::time(ptr::null_mut()) as i64
+    }
+}
+
+pub fn cputime() -> (Duration, Duration) {
+    let mut user = Duration::from_secs(0);
+    let mut system = Duration::from_secs(0);
+    for &who in &[libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN] {
+        let usage = unsafe {
+            let mut usage: libc::rusage = mem::zeroed();
+            libc::getrusage(who, &mut usage);
+            usage
+        };
+        user += Duration::new(usage.ru_utime.tv_sec as u64, usage.ru_utime.tv_usec as u32 * 1000);
+        system += Duration::new(usage.ru_stime.tv_sec as u64, usage.ru_stime.tv_usec as u32 * 1000);
+    }
+    (user, system)
+}
+
+pub struct Regex(libc::regex_t);
+
+impl Regex {
+    pub fn new(pattern: &str) -> Option<Self> {
+        let pattern = CString::new(pattern).ok()?;
+        unsafe {
+            let mut regex = mem::zeroed();
+            if libc::regcomp(&mut regex, pattern.as_ptr(), libc::REG_EXTENDED | libc::REG_ICASE | libc::REG_NOSUB) != 0 {
+                return None;
+            }
+            Some(Regex(regex))
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let text = match CString::new(text) {
+            Ok(text) => text,
+            Err(_) => return false,
+        };
+        unsafe {
+            libc::regexec(&self.0, text.as_ptr(), 0, ptr::null_mut(), 0) == 0
+        }
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        unsafe {
+            libc::regfree(&mut self.0);
+        }
+    }
+}
+
+pub fn waitpid(pid: pid_t, status: &mut c_int, options: c_int) -> pid_t {
+    retry(|| unsafe {
+        libc::waitpid(pid, status, options)
+    })
+}
+
+pub fn exit_status(status: c_int) -> c_int {
+    if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        libc::WEXITSTATUS(status)
+    }
+}
+
+pub trait Sys {
+    fn close(&mut self, fd: c_int) -> c_int;
+    fn dup2(&mut self, src: c_int, dst: c_int) -> c_int;
+    fn execvp(&mut self, cmd: &[String]) -> c_int;
+    fn exit(&mut self, status: c_int) -> !;
+    fn _exit(&mut self, status: c_int) -> !;
+    fn fork(&mut self) -> pid_t;
+    fn kill(&mut self, pid: pid_t) -> c_int;
+    fn openr(&mut self, path: &str) -> c_int;
+    fn openw(&mut self, path: &str) -> c_int;
+    fn pipe(&mut self, fds: &mut [c_int; 2]) -> c_int;
+    fn spawn(&mut self, cmd: &[String], stdin: Redirect, stdout: Redirect) -> pid_t;
+    fn waitpid(&mut self, pid: pid_t, status: &mut c_int, options: c_int) -> pid_t;
+}
+
+pub struct Libc;
+
+impl Sys for Libc {
+    fn close(&mut self, fd: c_int) -> c_int {
+        close(fd)
+    }
+
+    fn dup2(&mut self, src: c_int, dst: c_int) -> c_int {
+        dup2(src, dst)
+    }
+
+    fn execvp(&mut self, cmd: &[String]) -> c_int {
+        execvp(cmd)
+    }
+
+    fn exit(&mut self, status: c_int) -> ! {
+        exit(status)
+    }
+
+    fn _exit(&mut self, status: c_int) -> ! {
+        _exit(status)
+    }
+
+    fn fork(&mut self) -> pid_t {
+        fork()
+    }
+
+    fn kill(&mut self, pid: pid_t) -> c_int {
+        kill(pid)
+    }
+
+    fn openr(&mut self, path: &str) -> c_int {
+        openr(path)
+    }
+
+    fn openw(&mut self, path: &str) -> c_int {
+        openw(path)
+    }
+
+    fn pipe(&mut self, fds: &mut [c_int; 2]) -> c_int {
+        pipe(fds)
+    }
+
+    fn spawn(&mut self, cmd: &[String], stdin: Redirect, stdout: Redirect) -> pid_t {
+        spawn(cmd, stdin, stdout)
+    }
+
+    fn waitpid(&mut self, pid: pid_t, status: &mut c_int, options: c_int) -> pid_t {
+        waitpid(pid, status, options)
+    }
+}
+
+#[derive(Default)]
+pub struct Mock {
+    pub calls: Vec<String>,
+    pub child: Option<usize>,
+    pub statuses: HashMap<pid_t, c_int>,
+    pub max_forks: Option<usize>,
+    pub posix_spawn: bool,
+    pub children: Vec<pid_t>,
+    forks: usize,
+    fds: c_int,
+}
+
+impl Mock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn fd(&mut self) -> c_int {
+        let fd = 10 + self.fds;
+        self.fds += 1;
+        fd
+    }
+}
+
+impl Sys for Mock {
+    fn close(&mut self, fd: c_int) -> c_int {
+        self.calls.push(format!("close({})", fd));
+        0
+    }
+
+    fn dup2(&mut self, src: c_int, dst: c_int) -> c_int {
+        self.calls.push(format!("dup2({}, {})", src, dst));
+        dst
+    }
+
+    fn execvp(&mut self, cmd: &[String]) -> c_int {
+        self.calls.push(format!("execvp({})", cmd.join(" ")));
+        set_errno(libc::ENOENT);
+        -1
+    }
+
+    fn exit(&mut self, status: c_int) -> ! {
+        self.calls.push(format!("exit({})", status));
+        panic!("exit({})", status);
+    }
+
+    fn _exit(&mut self, status: c_int) -> ! {
+        self.calls.push(format!("_exit({})", status));
+        panic!("_exit({})", status);
+    }
+
+    fn fork(&mut self) -> pid_t {
+        if self.max_forks == Some(self.forks) {
+            self.calls.push("fork() = -1".to_owned());
+            set_errno(libc::EAGAIN);
+            return -1;
+        }
+        let pid = if self.child == Some(self.forks) { 0 } else { 100 + self.forks as pid_t };
+        self.forks += 1;
+        if pid != 0 {
+            self.children.push(pid);
+        }
+        self.calls.push(format!("fork() = {}", pid));
+        pid
+    }
+
+    fn kill(&mut self, pid: pid_t) -> c_int {
+        self.calls.push(format!("kill({})", pid));
+        0
+    }
+
+    fn openr(&mut self, path: &str) -> c_int {
+        let fd = self.fd();
+        self.calls.push(format!("openr({}) = {}", path, fd));
+        fd
+    }
+
+    fn openw(&mut self, path: &str) -> c_int {
+        let fd = self.fd();
+        self.calls.push(format!("openw({}) = {}", path, fd));
+        fd
+    }
+
+    fn pipe(&mut self, fds: &mut [c_int; 2]) -> c_int {
+        *fds = [self.fd(), self.fd()];
+        self.calls.push(format!("pipe() = [{}, {}]", fds[0], fds[1]));
+        0
+    }
+
+    fn spawn(&mut self, cmd: &[String], stdin: Redirect, stdout: Redirect) -> pid_t {
+        if !self.posix_spawn {
+            set_errno(libc::ENOSYS);
+            return -1;
+        }
+        if self.max_forks == Some(self.forks) {
+            self.calls.push(format!("spawn({}, {:?}, {:?}) = -1", cmd.join(" "), stdin, stdout));
+            set_errno(libc::EAGAIN);
+            return -1;
+        }
+        let pid = 100 + self.forks as pid_t;
+        self.forks += 1;
+        self.children.push(pid);
+        self.calls.push(format!("spawn({}, {:?}, {:?}) = {}", cmd.join(" "), stdin, stdout, pid));
+        pid
+    }
+
+    fn waitpid(&mut self, pid: pid_t, status: &mut c_int, _options: c_int) -> pid_t {
+        if pid <= 0 {
+            let child = if self.children.is_empty() { -1 } else { self.children.remove(0) };
+            *status = self.statuses.get(&child).cloned().unwrap_or(0) << 8;
+            self.calls.push(format!("waitpid({}) = {}", pid, child));
+            return child;
+        }
+        self.children.retain(|&child| child != pid);
+        *status = self.statuses.get(&pid).cloned().unwrap_or(0) << 8;
+        self.calls.push(format!("waitpid({})", pid));
+        pid
+    }
+}
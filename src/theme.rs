@@ -0,0 +1,67 @@
+pub struct Theme {
+    pub name: &'static str,
+    segments: &'static [(&'static str, &'static str)],
+    separator: &'static str,
+    symbol: &'static str,
+    ok: &'static str,
+    error: &'static str,
+}
+
+pub const THEMES: &[Theme] = &[
+    Theme {
+        name: "minimal",
+        segments: &[("\\W", "1;34")],
+        separator: " ",
+        symbol: " \\$ ",
+        ok: "0",
+        error: "31",
+    },
+    Theme {
+        name: "classic",
+        segments: &[("\\u@\\h", "1;32"), ("\\w", "1;34")],
+        separator: ":",
+        symbol: "\\$ ",
+        ok: "0",
+        error: "0",
+    },
+    Theme {
+        name: "git",
+        segments: &[("\\w", "1;34"), ("\\g", "35"), ("\\?", "1;31")],
+        separator: " ",
+        symbol: " > ",
+        ok: "32",
+        error: "31",
+    },
+    Theme {
+        name: "informative",
+        segments: &[("\\A", "2"), ("\\u@\\h", "32"), ("\\w", "1;34"), ("\\g", "35"),
+                    ("\\j", "33"), ("\\E", "2"), ("\\?", "1;31")],
+        separator: " | ",
+        symbol: "\\n\\$ ",
+        ok: "32",
+        error: "31",
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Theme> {
+    THEMES.iter().find(|theme| theme.name == name)
+}
+
+fn color(text: &str, code: &str, colors: bool) -> String {
+    if !colors {
+        return text.to_owned();
+    }
+    format!("\x01\x1b[{}m\x02{}\x01\x1b[0m\x02", code, text)
+}
+
+impl Theme {
+    pub fn render<F: Fn(&str) -> String>(&self, expand: F, status: i32, colors: bool) -> String {
+        let segments: Vec<_> = self.segments.iter()
+            .map(|&(template, code)| (expand(template), code))
+            .filter(|(value, _)| !value.is_empty() && value != "0")
+            .map(|(value, code)| color(&value, code, colors))
+            .collect();
+        let symbol = color(&expand(self.symbol), if status == 0 { self.ok } else { self.error }, colors);
+        segments.join(self.separator) + &symbol
+    }
+}
@@ -0,0 +1,42 @@
+use shell::Rush;
+use std::fs::DirBuilder;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+
+impl Rush {
+    fn xdg_dir(&self, var: &str, fallback: &str) -> Option<String> {
+        match self.vars.get(var) {
+            Some(dir) if dir.starts_with('/') => Some(format!("{}/rush", dir)),
+            _ => self.vars.get("HOME").map(|home| format!("{}/{}/rush", home, fallback)),
+        }
+    }
+
+    fn xdg_file(&self, dir: Option<String>, name: &str, legacy: Option<&str>) -> Option<String> {
+        let legacy = legacy.and_then(|legacy| self.vars.get("HOME").map(|home| format!("{}/{}", home, legacy)));
+        let path = match dir {
+            Some(ref dir) => format!("{}/{}", dir, name),
+            None => return legacy,
+        };
+        match legacy {
+            Some(legacy) if !Path::new(&path).exists() && Path::new(&legacy).exists() => Some(legacy),
+            _ => Some(path),
+        }
+    }
+
+    pub fn config_dir(&self) -> Option<String> {
+        self.xdg_dir("XDG_CONFIG_HOME", ".config")
+    }
+
+    pub fn config_file(&self, name: &str, legacy: Option<&str>) -> Option<String> {
+        self.xdg_file(self.config_dir(), name, legacy)
+    }
+
+    pub fn data_file(&self, name: &str, legacy: Option<&str>) -> Option<String> {
+        let dir = self.xdg_dir("XDG_DATA_HOME", ".local/share");
+        let path = self.xdg_file(dir.clone(), name, legacy)?;
+        if let Some(dir) = dir.filter(|dir| path.starts_with(dir.as_str()) && !Path::new(dir).is_dir()) {
+            let _ = DirBuilder::new().recursive(true).mode(0o700).create(dir);
+        }
+        Some(path)
+    }
+}
@@ -0,0 +1,30 @@
+extern crate rush;
+
+use rush::alias;
+use rush::bash::{commands, split};
+use std::collections::HashMap;
+
+#[test]
+fn split_commands() {
+    assert_eq!(split("a; b 'c;d' \\; # e; f"), ["a", "b 'c;d' \\;"]);
+    assert_eq!(split("echo a#b \"#\" ;;"), ["echo a#b \"#\""]);
+    assert!(split("  # comment").is_empty());
+}
+
+#[test]
+fn skips_unsupported_constructs() {
+    let script = "alias a=b\nif x; then\n  y\nfi\nf() {\n  g\n}\nfunction h\n{\n  i; }\nfor x in 1; do j; done\nk";
+    assert_eq!(commands(script), [Ok("alias a=b"), Err(2), Err(5), Err(8), Err(11), Ok("k")]);
+}
+
+#[test]
+fn expand_aliases() {
+    let mut aliases = HashMap::new();
+    assert_eq!(alias::expand("ll", &aliases), None);
+    aliases.insert("ll".to_owned(), "ls -l".to_owned());
+    aliases.insert("la".to_owned(), "ll -a".to_owned());
+    aliases.insert("ls".to_owned(), "ls --color".to_owned());
+    assert_eq!(alias::expand("la /tmp | ll && echo ll", &aliases).unwrap(),
+               "ls --color -l -a /tmp | ls --color -l && echo ll");
+    assert_eq!(alias::expand("echo ll", &aliases), None);
+}
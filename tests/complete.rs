@@ -0,0 +1,51 @@
+extern crate rush;
+
+use rush::shell::Rush;
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[test]
+fn bash_completion_script() {
+    let path = format!("{}/rushtest-completion.bash", env!("CARGO_TARGET_TMPDIR"));
+    fs::write(&path, "_rushtest() {\n  local cur=${COMP_WORDS[COMP_CWORD]}\n  \
+                      COMPREPLY=($(compgen -W \"start stop $COMP_CWORD\" -- \"$cur\"))\n}\n\
+                      complete -o default -F _rushtest rushtest\ncomplete -f -X '*.o' rushskip\n\
+                      _rushslow() {\n  sleep 10\n}\ncomplete -F _rushslow rushslow\n").unwrap();
+    let mut rush = Rush::new();
+    rush.execute(&format!("complete --load {}", path));
+    let spec = rush.completions.get("rushtest").unwrap().clone();
+    assert_eq!(spec.to_args(), "-o default -F _rushtest");
+    let words = ["rushtest".to_owned(), "st".to_owned()];
+    assert_eq!(rush.compgen(&spec, "st", &words, 1), ["start", "stop"]);
+    let words = ["rushtest".to_owned(), "a".to_owned(), "".to_owned()];
+    assert_eq!(rush.compgen(&spec, "", &words, 2), ["start", "stop", "2"]);
+    assert!(!rush.completions.contains_key("rushskip"));
+    let spec = rush.completions.get("rushslow").unwrap().clone();
+    let start = Instant::now();
+    assert!(rush.compgen(&spec, "", &["rushslow".to_owned(), "".to_owned()], 1).is_empty());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn suggests_close_commands() {
+    let mut rush = Rush::new();
+    rush.vars.set("PATH", "");
+    rush.aliases.insert("gst".to_owned(), "git status".to_owned());
+    assert_eq!(rush.suggest_commands("histroy"), ["history"]);
+    assert_eq!(rush.suggest_commands("gsst"), ["gst"]);
+    assert_eq!(rush.suggest_commands("popdd"), ["popd"]);
+    assert!(rush.suggest_commands("qqqqqq").is_empty());
+}
+
+#[test]
+fn command_sees_word_boundaries() {
+    use std::os::unix::fs::PermissionsExt;
+    let path = format!("{}/rushtest-words.sh", env!("CARGO_TARGET_TMPDIR"));
+    fs::write(&path, "#!/bin/sh\neval \"set -- $COMP_WORDS\"\necho \"$#:$2\"\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    let mut rush = Rush::new();
+    rush.execute(&format!("complete -C {} rushwords", path));
+    let spec = rush.completions.get("rushwords").unwrap().clone();
+    let words = ["rushwords".to_owned(), "a b".to_owned(), "x".to_owned()];
+    assert_eq!(rush.compgen(&spec, "x", &words, 2), ["3:a\\ b"]);
+}
@@ -0,0 +1,228 @@
+extern crate rush;
+
+use rush::parser::parse;
+use rush::shell::Rush;
+use rush::sys::Mock;
+use std::panic::{self, AssertUnwindSafe};
+
+fn run(line: &str, sys: &mut Mock) -> Rush {
+    let mut rush = Rush::new();
+    for list in parse(line).unwrap() {
+        rush.exec(&list, sys).unwrap();
+    }
+    rush
+}
+
+fn run_child(line: &str, child: usize) -> Vec<String> {
+    let mut sys = Mock::new();
+    sys.child = Some(child);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run(line, &mut sys);
+    }));
+    assert!(result.is_err(), "child did not exit");
+    sys.calls
+}
+
+#[test]
+fn single_command() {
+    let mut sys = Mock::new();
+    run("ls -l", &mut sys);
+    assert_eq!(sys.calls, ["fork() = 100", "waitpid(100)"]);
+}
+
+#[test]
+fn pipeline_parent() {
+    let mut sys = Mock::new();
+    run("a | b | c", &mut sys);
+    assert_eq!(sys.calls, [
+        "pipe() = [10, 11]",
+        "pipe() = [12, 13]",
+        "fork() = 100",
+        "close(11)",
+        "fork() = 101",
+        "close(10)",
+        "close(13)",
+        "fork() = 102",
+        "close(12)",
+        "waitpid(100)",
+        "waitpid(101)",
+        "waitpid(102)",
+    ]);
+}
+
+#[test]
+fn pipeline_middle_child() {
+    let calls = run_child("a | b | c", 1);
+    assert_eq!(&calls[4 ..], [
+        "fork() = 0",
+        "dup2(10, 0)",
+        "dup2(13, 1)",
+        "close(10)",
+        "close(12)",
+        "close(13)",
+        "execvp(b)",
+        "_exit(127)",
+    ]);
+}
+
+#[test]
+fn redirections() {
+    let calls = run_child("sort < in | uniq > out", 0);
+    assert_eq!(&calls[1 ..], [
+        "fork() = 0",
+        "openr(in) = 12",
+        "dup2(12, 0)",
+        "close(12)",
+        "dup2(11, 1)",
+        "close(10)",
+        "close(11)",
+        "execvp(sort)",
+        "_exit(127)",
+    ]);
+    let calls = run_child("sort < in | uniq > out", 1);
+    assert_eq!(&calls[3 ..], [
+        "fork() = 0",
+        "dup2(10, 0)",
+        "openw(out) = 12",
+        "dup2(12, 1)",
+        "close(12)",
+        "close(10)",
+        "execvp(uniq)",
+        "_exit(127)",
+    ]);
+}
+
+#[test]
+fn exit_status() {
+    let mut sys = Mock::new();
+    sys.statuses.insert(101, 3);
+    let rush = run("true | false", &mut sys);
+    assert_eq!(rush.status, 3);
+}
+
+#[test]
+fn background_job() {
+    let mut sys = Mock::new();
+    let rush = run("sleep 10 &", &mut sys);
+    assert_eq!(sys.calls, ["fork() = 100"]);
+    assert_eq!(rush.status, 0);
+    assert_eq!(rush.jobs.len(), 1);
+    let job = rush.jobs.iter().next().unwrap();
+    assert_eq!(job.pids, [100]);
+    assert_eq!(job.cmd, "sleep 10");
+}
+
+#[test]
+fn builtin_runs_in_process() {
+    let mut sys = Mock::new();
+    let rush = run("X=1", &mut sys);
+    assert!(sys.calls.is_empty());
+    assert_eq!(rush.vars.get("X"), Some("1"));
+}
+
+#[test]
+fn builtin_in_pipeline() {
+    let calls = run_child("pwd | cat", 0);
+    assert_eq!(&calls[1 ..], [
+        "fork() = 0",
+        "dup2(11, 1)",
+        "close(10)",
+        "close(11)",
+        "_exit(0)",
+    ]);
+}
+
+#[test]
+fn background_separator() {
+    let mut sys = Mock::new();
+    let rush = run("make & tail log", &mut sys);
+    assert_eq!(sys.calls, ["fork() = 100", "fork() = 101", "waitpid(101)"]);
+    assert_eq!(rush.jobs.len(), 1);
+    assert_eq!(rush.jobs.iter().next().unwrap().pids, [100]);
+}
+
+#[test]
+fn background_job_text() {
+    let mut sys = Mock::new();
+    let rush = run("echo 'a&b'  &  sort < in |  uniq > out &", &mut sys);
+    let cmds: Vec<_> = rush.jobs.iter().map(|job| job.cmd.as_str()).collect();
+    assert_eq!(cmds, ["echo 'a&b'", "sort < in | uniq > out"]);
+}
+
+#[test]
+fn and_or_lists() {
+    let mut sys = Mock::new();
+    sys.statuses.insert(100, 1);
+    let rush = run("false && a || b", &mut sys);
+    assert_eq!(sys.calls, ["fork() = 100", "waitpid(100)", "fork() = 101", "waitpid(101)"]);
+    assert_eq!(rush.status, 0);
+}
+
+#[test]
+fn failing_builtin_status() {
+    let mut sys = Mock::new();
+    let rush = run("cd /nonexistent || X=1", &mut sys);
+    assert!(sys.calls.is_empty());
+    assert_eq!(rush.vars.get("X"), Some("1"));
+    let rush = run("jobs extra && X=1", &mut sys);
+    assert_eq!(rush.status, 1);
+    assert_eq!(rush.vars.get("X"), None);
+}
+
+#[test]
+fn background_and_or_list() {
+    let mut sys = Mock::new();
+    let rush = run("a && b &", &mut sys);
+    assert_eq!(sys.calls, ["fork() = 100"]);
+    assert_eq!(rush.jobs.iter().next().unwrap().cmd, "a && b");
+}
+
+#[test]
+fn fork_failure() {
+    let mut sys = Mock::new();
+    sys.max_forks = Some(1);
+    let mut rush = Rush::new();
+    let list = parse("a | b | c &").unwrap().remove(0);
+    let error = rush.exec(&list, &mut sys).unwrap_err();
+    assert!(error.to_string().starts_with("Spawn Error: fork: "));
+    assert_eq!(sys.calls, [
+        "pipe() = [10, 11]",
+        "pipe() = [12, 13]",
+        "fork() = 100",
+        "close(11)",
+        "fork() = -1",
+        "close(10)",
+        "close(12)",
+        "close(13)",
+        "waitpid(100)",
+    ]);
+    assert!(rush.jobs.is_empty());
+}
+
+#[test]
+fn posix_spawn() {
+    let mut sys = Mock::new();
+    sys.posix_spawn = true;
+    run("sort < in | uniq > out", &mut sys);
+    assert_eq!(sys.calls, [
+        "pipe() = [10, 11]",
+        "spawn(sort, Read(\"in\"), Fd(11)) = 100",
+        "close(11)",
+        "spawn(uniq, Fd(10), Write(\"out\")) = 101",
+        "close(10)",
+        "waitpid(100)",
+        "waitpid(101)",
+    ]);
+}
+
+#[test]
+fn posix_spawn_skips_builtins() {
+    let mut sys = Mock::new();
+    sys.posix_spawn = true;
+    run("pwd | cat", &mut sys);
+    assert_eq!(&sys.calls[1 .. 4], [
+        "fork() = 100",
+        "close(11)",
+        "spawn(cat, Fd(10), Fd(1)) = 101",
+    ]);
+}
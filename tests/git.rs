@@ -0,0 +1,19 @@
+extern crate rush;
+
+use rush::git;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn large_status_output() {
+    let dir = format!("{}/git-large", env!("CARGO_TARGET_TMPDIR"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    assert!(Command::new("git").args(["init", "-q", "-b", "master"]).current_dir(&dir).status().unwrap().success());
+    for i in 0 .. 2000 {
+        fs::write(format!("{}/untracked-file-with-a-long-name-to-fill-the-pipe-buffer-{:04}", dir, i), "").unwrap();
+    }
+    let output = Command::new("git").args(["status", "--porcelain=v2"]).current_dir(&dir).output().unwrap();
+    assert!(output.stdout.len() > 65536);
+    assert_eq!(git::prompt(&dir), "master%");
+}
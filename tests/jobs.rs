@@ -0,0 +1,44 @@
+extern crate rush;
+
+use rush::jobs::{Jobs, State};
+use rush::sys::Mock;
+
+#[test]
+fn reaped_pids_are_not_polled_again() {
+    let mut jobs = Jobs::new();
+    jobs.push(vec![100, 101], "a | b".to_owned());
+    let mut sys = Mock::new();
+    sys.children = vec![100, 101];
+    sys.statuses.insert(101, 2);
+    jobs.update(&mut sys);
+    jobs.update(&mut sys);
+    assert_eq!(sys.calls, ["waitpid(0) = 100", "waitpid(0) = 101", "waitpid(0) = -1", "waitpid(0) = -1"]);
+    assert_eq!(jobs.get(1).unwrap().state, State::Done(2));
+    assert_eq!(jobs.pids().count(), 0);
+    let done = jobs.reap();
+    assert_eq!(done.len(), 1);
+    assert!(jobs.is_empty());
+}
+
+#[test]
+fn job_ids() {
+    let mut jobs = Jobs::new();
+    jobs.push(vec![100], "a".to_owned());
+    jobs.push(vec![101], "b".to_owned());
+    assert_eq!(jobs.get(2).unwrap().cmd, "b");
+    assert_eq!(jobs.get(2).unwrap().state.to_string(), "Running");
+    assert!(jobs.get(3).is_none());
+}
+
+#[test]
+fn finds_jobs_by_spec() {
+    let mut jobs = Jobs::new();
+    jobs.push(vec![100], "sleep 10".to_owned());
+    jobs.push(vec![101], "make -j4".to_owned());
+    jobs.push(vec![102], "make check".to_owned());
+    assert_eq!(jobs.find("2").unwrap().cmd, "make -j4");
+    assert_eq!(jobs.find("sl").unwrap().id, 1);
+    assert!(jobs.find("make").is_none());
+    assert!(jobs.find("4").is_none());
+    assert!(jobs.find("").is_none());
+}
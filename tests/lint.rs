@@ -0,0 +1,43 @@
+extern crate rush;
+
+use rush::lint::lint;
+
+fn messages(script: &str) -> Vec<String> {
+    lint(script).into_iter().map(|lint| format!("{}: {}", lint.line, lint.message)).collect()
+}
+
+#[test]
+fn unassigned_variables() {
+    assert_eq!(messages("echo $RUSH_LINT_A\nRUSH_LINT_B=1\necho ${RUSH_LINT_B} '$RUSH_LINT_C' \\$RUSH_LINT_D $PWD"), [
+        "1: RUSH_LINT_A is referenced but never assigned (column 6)",
+    ]);
+}
+
+#[test]
+fn unreachable_after_exit() {
+    assert_eq!(messages("echo a\nexit\necho b\necho c"), ["3: unreachable command after exit (column 1)"]);
+    assert_eq!(messages("exec ls & echo b\nfalse || exit\necho c"), Vec::<String>::new());
+}
+
+#[test]
+fn useless_cat() {
+    assert_eq!(messages("cat log | grep x\ncat -n log | grep x\ncat a b | wc"), [
+        "1: useless use of cat, redirect log with < instead (column 1)",
+    ]);
+}
+
+#[test]
+fn clobbered_files() {
+    assert_eq!(messages("sort < f > f\na > out && b > out\na > out\nb > out"), [
+        "1: f is truncated before it is read (column 12)",
+        "2: out is truncated twice on the same line (column 16)",
+    ]);
+}
+
+#[test]
+fn syntax_errors() {
+    assert_eq!(messages("a |\necho $RUSH_LINT_E"), [
+        "1: Parsing Error: | cannot appear as the last word in a command (column 3)",
+        "2: RUSH_LINT_E is referenced but never assigned (column 6)",
+    ]);
+}
@@ -0,0 +1,125 @@
+extern crate rush;
+
+use rush::error::ShellError;
+use rush::lexer::{tokenize, Token};
+use rush::parser::{parse, CmdLine, Op, Time};
+
+fn parse_one(line: &str) -> CmdLine<&str> {
+    let mut lists = parse(line).unwrap();
+    assert_eq!(lists.len(), 1);
+    let list = lists.remove(0);
+    assert!(list.rest.is_empty());
+    list.first
+}
+
+fn parse_error(line: &str) -> String {
+    match parse(line) {
+        Err(error @ ShellError::Parse(_)) => error.to_string(),
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("{:?} parsed", line),
+    }
+}
+
+#[test]
+fn redirection_without_command() {
+    let cmdline = parse_one("> out");
+    assert!(cmdline.is_empty());
+    assert_eq!(cmdline.fileout, Some("out"));
+}
+
+#[test]
+fn missing_filename() {
+    assert_eq!(parse_error(">"), "Parsing Error: No filename after > (column 1)");
+    assert_eq!(parse_error("<"), "Parsing Error: No filename after < (column 1)");
+    assert_eq!(parse_error("a > |"), "Parsing Error: Illegal filename after > (column 3)");
+}
+
+#[test]
+fn empty_pipeline_stage() {
+    parse_error("|");
+    parse_error("a | | b");
+    parse_error("a |");
+    parse_error("a | &");
+    parse_error("a & & b");
+    assert_eq!(parse_error("a | > out"), "Parsing Error: | cannot appear as the last word in a command (column 3)");
+}
+
+#[test]
+fn nul_byte() {
+    assert_eq!(parse_error("echo a\0b"), "Parsing Error: nul byte found in the input");
+}
+
+#[test]
+fn error_column() {
+    assert_eq!(parse_error("écho ü < in | cat < in"),
+               "Parsing Error: < can appear only in the first command (column 19)");
+}
+
+#[test]
+fn token_spans() {
+    let tokens: Vec<_> = tokenize("  ls  'a b'\\ c|d ").collect();
+    assert_eq!(tokens, [
+        Token { text: "ls", span: 2 .. 4 },
+        Token { text: "'a b'\\ c|d", span: 6 .. 16 },
+    ]);
+}
+
+#[test]
+fn unterminated_quotes() {
+    let cmdline = parse_one("echo 'a b \"c");
+    assert_eq!(cmdline.cmds[0].cmd, ["echo", "'a b \"c"]);
+}
+
+#[test]
+fn multibyte_words() {
+    let cmdline = parse_one("écho\u{2022}ü|ß>ñ");
+    assert_eq!(cmdline.cmds[0].cmd, ["écho\u{2022}ü|ß>ñ"]);
+    let cmdline = parse_one("écho ü | ß > ñ");
+    assert_eq!(cmdline.len(), 2);
+    assert_eq!(cmdline.cmds[1].prog(), "ß");
+}
+
+#[test]
+fn background_separator() {
+    let lists = parse("make -j4 & tail -f log &").unwrap();
+    assert_eq!(lists.len(), 2);
+    assert!(lists[0].back && lists[1].back);
+    assert_eq!(lists[1].first.cmds[0].cmd, ["tail", "-f", "log"]);
+    assert!(!parse("a & b").unwrap()[1].back);
+    assert_eq!(parse_error("& a"), "Parsing Error: & cannot appear as the first word in a command (column 1)");
+}
+
+#[test]
+fn and_or_lists() {
+    let lists = parse("a && b | c || d > out &").unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0].rest.len(), 2);
+    assert_eq!(lists[0].rest[0].0, Op::And);
+    assert_eq!(lists[0].rest[1].0, Op::Or);
+    assert_eq!(lists[0].to_string(), "a && b | c || d > out");
+    assert_eq!(parse_error("&& a"), "Parsing Error: && cannot appear as the first word in a command (column 1)");
+    assert_eq!(parse_error("a || || b"), "Parsing Error: || cannot appear as the first word in a command (column 6)");
+    assert_eq!(parse_error("a ||"), "Parsing Error: || cannot appear as the last word in a command (column 3)");
+    parse_error("a && &");
+}
+
+#[test]
+fn words_borrow_from_line() {
+    let line = String::from("sort < in | uniq -c > out");
+    let cmdline = parse_one(&line);
+    assert_eq!(cmdline.cmds[1].cmd, ["uniq", "-c"]);
+    assert_eq!(cmdline.cmds[1].cmd[1].as_ptr(), line[17 ..].as_ptr());
+    assert_eq!(cmdline.filein.unwrap().as_ptr(), line[7 ..].as_ptr());
+}
+
+#[test]
+fn time_keyword() {
+    let cmdline = parse_one("time sort < in | uniq");
+    assert_eq!(cmdline.time, Some(Time::Default));
+    assert_eq!(cmdline.cmds[0].cmd, ["sort"]);
+    assert_eq!(parse_one("time -p true").time, Some(Time::Posix));
+    assert_eq!(parse_one("time").len(), 0);
+    assert_eq!(parse_one("echo time").time, None);
+    assert_eq!(parse("a && time -p b").unwrap()[0].to_string(), "a && time -p b");
+    assert_eq!(parse_error("time | a"), "Parsing Error: | cannot appear as the first word in a command (column 6)");
+}
@@ -0,0 +1,35 @@
+extern crate rush;
+
+use rush::pathindex::PathIndex;
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn touch(path: &str, mode: u32) {
+    fs::write(path, "").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn set_mtime(path: &str, secs: u64) {
+    File::open(path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+}
+
+#[test]
+fn rescans_changed_dirs() {
+    let dir = format!("{}/pathindex", env!("CARGO_TARGET_TMPDIR"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(format!("{}/a", dir)).unwrap();
+    fs::create_dir_all(format!("{}/b", dir)).unwrap();
+    touch(&format!("{}/a/foo", dir), 0o755);
+    touch(&format!("{}/a/data", dir), 0o644);
+    touch(&format!("{}/b/foo", dir), 0o755);
+    set_mtime(&format!("{}/b", dir), 1000);
+    let path = format!("{0}/a:{0}/b", dir);
+    let index = PathIndex::new();
+    index.prefetch(&path);
+    assert_eq!(index.commands(&path), ["foo"]);
+    touch(&format!("{}/b/bar", dir), 0o755);
+    set_mtime(&format!("{}/b", dir), 2000);
+    assert_eq!(index.commands(&path), ["bar", "foo"]);
+    assert_eq!(index.commands(&format!("{}/a", dir)), ["foo"]);
+}
@@ -0,0 +1,22 @@
+extern crate rush;
+
+use rush::pattern::{Pattern, PatternCache};
+
+#[test]
+fn glob_syntax() {
+    assert!(Pattern::new("ls*").matches("ls -l"));
+    assert!(Pattern::new("?d").matches("cd"));
+    assert!(Pattern::new("[a-c]at").matches("bat"));
+    assert!(!Pattern::new("[!a-c]at").matches("bat"));
+    assert!(Pattern::new("\\*").matches("*"));
+    assert!(!Pattern::new("ls").matches("ls -l"));
+}
+
+#[test]
+fn compiled_once() {
+    let mut cache = PatternCache::new();
+    assert!(cache.get("ls*").matches("ls"));
+    assert!(cache.get("ls*").matches("lsof"));
+    assert!(!cache.get("cd").matches("ls"));
+    assert_eq!(cache.len(), 2);
+}
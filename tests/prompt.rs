@@ -0,0 +1,36 @@
+extern crate rush;
+
+use rush::prompt::AsyncSegment;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn slow(key: &str) -> String {
+    thread::sleep(Duration::from_millis(200));
+    format!("{}{}", key, CALLS.fetch_add(1, Ordering::SeqCst) + 1)
+}
+
+fn fast(key: &str) -> String {
+    key.to_uppercase()
+}
+
+#[test]
+fn async_segment() {
+    let segment = AsyncSegment::new();
+    assert_eq!(segment.get("a", slow), "");
+    assert!(segment.is_pending());
+    assert!(segment.wait(Duration::from_secs(5)));
+    assert!(!segment.is_pending());
+    assert_eq!(segment.get("a", slow), "a1");
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    segment.invalidate();
+    assert_eq!(segment.get("a", slow), "a1");
+    assert!(segment.is_pending());
+    assert_eq!(segment.get("b", fast), "");
+    assert!(segment.wait(Duration::from_secs(5)));
+    assert_eq!(segment.get("a", slow), "a2");
+    assert_eq!(segment.get("b", fast), "B");
+    assert!(!segment.wait(Duration::from_secs(0)));
+}
@@ -0,0 +1,34 @@
+extern crate rush;
+
+use rush::shell::Rush;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn save_and_restore() {
+    let root = format!("{}/session", env!("CARGO_TARGET_TMPDIR"));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(format!("{}/work", root)).unwrap();
+    let mut rush = Rush::new();
+    rush.vars.set("HOME", &root);
+    rush.vars.set("XDG_DATA_HOME", &format!("{}/data", root));
+    rush.execute(&format!("cd {}/work", root));
+    rush.execute("pushd /");
+    rush.execute("sleep 5 &");
+    rush.status = 3;
+    rush.save_session().unwrap();
+    rush.execute("kill %1");
+    let saved = fs::read_to_string(format!("{}/data/rush/session", root)).unwrap();
+    assert_eq!(saved, format!("status 3\ncwd /\ndir {}/work\njob sleep 5\n", root));
+    let mode = fs::metadata(format!("{}/data/rush/session", root)).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    fs::write(format!("{}/data/rush/session", root), format!("status 7\ncwd {0}/work\ndir /\ndir {0}/gone\n", root)).unwrap();
+    let mut rush = Rush::new();
+    rush.vars.set("HOME", &root);
+    rush.vars.set("XDG_DATA_HOME", &format!("{}/data", root));
+    rush.restore_session();
+    assert_eq!(rush.status, 7);
+    assert_eq!(rush.cwd, format!("{}/work", root));
+    assert_eq!(rush.dirs, ["/"]);
+}
@@ -0,0 +1,417 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn rush(input: &[u8]) -> (i32, String, String) {
+    rush_with(&[], input)
+}
+
+fn rush_with(args: &[&str], input: &[u8]) -> (i32, String, String) {
+    rush_env(args, &[], input)
+}
+
+fn rush_env(args: &[&str], vars: &[(&str, &str)], input: &[u8]) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rush"))
+        .args(args)
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .envs(vars.iter().cloned())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.code().unwrap_or(-1),
+     String::from_utf8_lossy(&output.stdout).into_owned(),
+     String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn invalid_utf8() {
+    let (status, stdout, _) = rush(b"echo \xff\xfe\necho ok\n");
+    assert_eq!(status, 0);
+    assert!(stdout.contains("\u{fffd}\u{fffd}\n"));
+    assert!(stdout.contains("ok\n"));
+}
+
+#[test]
+fn empty_command() {
+    let (status, stdout, stderr) = rush(b"$EMPTY_COMMAND\n$EMPTY_COMMAND | cat\necho ok\n");
+    assert_eq!(status, 0);
+    assert!(stdout.contains("ok\n"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn syntax_error() {
+    let (status, stdout, stderr) = rush(b"a |\necho ok\n");
+    assert_eq!(status, 2);
+    assert!(!stdout.contains("ok"));
+    assert!(stderr.contains("Parsing Error"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn debug_parse() {
+    let (status, stdout, _) = rush(b"debug tokens 'ls  -l | wc'\n");
+    assert_eq!(status, 0);
+    assert!(stdout.contains("0..2\tls\n4..6\t-l\n7..8\t|\n9..11\twc\n"));
+    let (_, stdout, _) = rush(b"debug ast 'sort < in | uniq &'\n");
+    assert!(stdout.contains("back: true"));
+    assert!(stdout.contains("\"uniq\""));
+}
+
+#[test]
+fn hooks() {
+    let (status, stdout, stderr) = rush(b"hook on_chdir 'echo in $PWD'\nhook on_exit echo bye\ncd /\nhook nope x\n");
+    assert_eq!(status, 0);
+    assert!(stdout.contains("in /\n"));
+    assert!(stdout.ends_with("bye\n"));
+    assert!(stderr.contains("hook: nope: unknown hook"));
+}
+
+#[test]
+fn errexit_runs_exit_hooks() {
+    let (status, stdout, _) = rush(b"hook on_exit echo bye\nset -e\nfalse\necho no\n");
+    assert_eq!(status, 1);
+    assert_eq!(stdout.replace("$ ", ""), "bye\n");
+    let (status, stdout, _) = rush(b"hook on_exit echo bye\necho a ||\necho no\n");
+    assert_eq!(status, 2);
+    assert_eq!(stdout.replace("$ ", ""), "bye\n");
+}
+
+#[test]
+fn exec_failure_status() {
+    let (_, stdout, stderr) = rush(b"rush-no-such-command\necho $?\n/\necho $?\n");
+    assert!(stdout.contains("127\n"));
+    assert!(stdout.contains("126\n"));
+    assert!(stderr.contains("rush-no-such-command: No such file or directory"));
+}
+
+#[test]
+fn jobs_in_pipeline() {
+    let (_, stdout, _) = rush(b"sleep 1 &\njobs | cat\n");
+    assert!(stdout.contains("sleep 1\n"));
+}
+
+#[test]
+fn no_inherited_fds() {
+    let (_, stdout, _) = rush(b"ls /proc/self/fd < /dev/null | cat\n");
+    assert!(stdout.contains("0\n1\n2\n3\n"));
+    assert!(!stdout.contains("4\n"));
+}
+
+#[test]
+fn buffered_builtin_output() {
+    let (status, stdout, _) = rush(b"set -o history\necho one\nfc -s echo\nX=1\nset | grep '^X='\n");
+    assert_eq!(status, 0);
+    assert_eq!(stdout.replace("$ ", ""), "one\necho one\none\nX=1\n");
+}
+
+#[test]
+fn startup_profile() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rush"))
+        .arg("--startup-profile")
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stages: Vec<_> = stderr.lines().filter_map(|line| line.split_whitespace().nth(1)).collect();
+    assert_eq!(stages, ["vars", "builtins", "config", "on_startup", "prompt", "total"]);
+}
+
+#[test]
+fn structured_output() {
+    let (_, stdout, _) = rush(b"sleep 1 &\njobs --format json\njobs --porcelain\n");
+    let json = stdout.lines().find(|line| line.contains("{")).unwrap().replace("$ ", "");
+    assert!(json.starts_with("{\"id\":1,\"pgid\":"));
+    assert!(json.contains(",\"state\":\"running\",\"status\":null,\"command\":\"sleep 1\",\"started\":"));
+    assert!(stdout.replace("$ ", "").lines().any(|line| line.starts_with("1\t") && line.contains("\trunning\t-1\t") && line.ends_with("\tsleep 1")));
+    let (_, stdout, _) = rush(b"set -o history\nhistory -c\necho \"a\tb\"\nhistory --format=json\nhistory --porcelain 1\n");
+    assert!(stdout.contains("{\"number\":2,\"time\":"));
+    assert!(stdout.contains(",\"command\":\"echo \\\"a\\tb\\\"\"}\n"));
+    assert!(stdout.contains("3\t"));
+    assert!(stdout.contains("\thistory --format=json\n"));
+    let (_, stdout, _) = rush(b"set -o history\nhistory -c\necho 'a\tb\\c'\nhistory --porcelain 2\n");
+    assert!(stdout.contains("\techo 'a\\tb\\\\c'\n"));
+    let (_, _, stderr) = rush(b"jobs --format yaml\n");
+    assert!(stderr.contains("jobs: yaml: unknown format"));
+}
+
+#[test]
+fn lint_mode() {
+    let (status, stdout, _) = rush_with(&["--lint"], b"cat f | wc\n");
+    assert_eq!(status, 1);
+    assert_eq!(stdout, "-:1: useless use of cat, redirect f with < instead (column 1)\n");
+}
+
+#[test]
+fn posix_mode() {
+    let (status, stdout, _) = rush_with(&["--posix"], b"hook on_exit echo bye\nhash -d rushposixdir=/\necho a$1b $# ~rushposixdir\nset -u\necho $-\n");
+    assert_eq!(status, 0);
+    assert_eq!(stdout, "ab 0 ~rushposixdir\nu\nbye\n");
+    let (status, _, stderr) = rush_with(&["--posix"], b"echo ${x:-y}\necho no\n");
+    assert_eq!(status, 1);
+    assert!(stderr.contains("${x:-y}: bad substitution"));
+    let (status, stdout, stderr) = rush_with(&[], b"set -o posix\nif true\necho no\n");
+    assert_eq!(status, 2);
+    assert!(!stdout.contains("no"));
+    assert!(stderr.contains("Parsing Error: if: reserved word is not supported"));
+    let (_, stdout, _) = rush(b"echo $0 $# $1x\n");
+    assert_eq!(stdout.replace("$ ", ""), format!("{} 0 x\n", env!("CARGO_BIN_EXE_rush")));
+    let (status, stdout, stderr) = rush_with(&["--posix", "script.sh"], b"echo no\n");
+    assert_eq!(status, 2);
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("rush: script.sh: script files are not supported"));
+}
+
+#[test]
+fn time_keyword() {
+    let (status, stdout, stderr) = rush(b"time -p echo hi | cat\nTIMEFORMAT='[%0R %0lS]\\t%%'\ntime false\nTIMEFORMAT=\ntime true\n");
+    assert_eq!(status, 0);
+    assert!(stdout.contains("hi\n"));
+    assert!(stderr.starts_with("real 0.0"));
+    assert!(stderr.contains("\nuser 0.0"));
+    assert!(stderr.ends_with("[0 0m0s]\t%\n"));
+}
+
+#[test]
+fn bind_keys() {
+    let path = format!("{}/inputrc", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "$if Rush\nControl-o: kill-whole-line\n$else\n\"\\C-o\": undo\n$endif\n\"\\ex\": nosuch\n").unwrap();
+    let input = format!("bind '\"\\C-g\": clear-line' 'M-DEL: unix-word-rubout'\nbind -r '\\C-a'\nbind -f {}\nbind -p\n", path);
+    let (status, stdout, stderr) = rush(input.as_bytes());
+    assert_eq!(status, 0);
+    assert!(stdout.contains("\"\\C-g\": clear-line\n"));
+    assert!(stdout.contains("\"\\e\\C-?\": unix-word-rubout\n"));
+    assert!(stdout.contains("\"\\C-o\": kill-whole-line\n"));
+    assert!(stdout.contains("\"\\e[H\": beginning-of-line\n"));
+    assert!(!stdout.contains("\"\\C-a\""));
+    assert!(stderr.contains(&format!("bind: {}: line 6: nosuch: unknown function name", path)));
+    let (_, _, stderr) = rush(b"bind '\"\\C-xq\": \"ls\"'\n");
+    assert!(stderr.contains("bind: \"\\C-xq\": macros are not supported"));
+}
+
+#[test]
+fn logout() {
+    let home = format!("{}/login_home", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::create_dir_all(&home).unwrap();
+    std::fs::write(format!("{}/.rush_logout", home), "echo bye from logout\nexit\n").unwrap();
+    let input = format!("HOME={}\nhook on_exit echo hook\nlogout\necho no\n", home);
+    let (status, stdout, _) = rush_with(&["-l"], input.as_bytes());
+    assert_eq!(status, 0);
+    assert_eq!(stdout.replace("$ ", ""), "bye from logout\nhook\n");
+    let input_eof = format!("HOME={}\nhook on_exit echo hook\n", home);
+    let (_, stdout, _) = rush_with(&["--login"], input_eof.as_bytes());
+    assert!(stdout.ends_with("bye from logout\nhook\n"));
+    let (_, stdout, stderr) = rush(input.as_bytes());
+    assert!(stderr.contains("logout: not login shell: use `exit`"));
+    assert_eq!(stdout.replace("$ ", ""), "no\nhook\n");
+    let home = format!("{}/login_bashcompat", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::create_dir_all(&home).unwrap();
+    std::fs::write(format!("{}/.rush_logout", home), "echo a; echo b\n").unwrap();
+    let input = format!("HOME={}\nset -o bashcompat\nlogout\n", home);
+    let (_, stdout, _) = rush_with(&["-l"], input.as_bytes());
+    assert_eq!(stdout.replace("$ ", ""), "a\nb\n");
+}
+
+#[test]
+fn repeat() {
+    let (status, stdout, stderr) = rush(b"repeat 3 echo 'a  b' $HOME | cat\nrepeat 0 echo no\nrepeat x echo no\nrepeat 2 false\necho $?\n");
+    assert_eq!(status, 0);
+    let home = env!("CARGO_TARGET_TMPDIR");
+    assert_eq!(stdout.replace("$ ", ""), format!("a  b {0}\na  b {0}\na  b {0}\n1\n", home));
+    assert!(stderr.contains("repeat: x: numeric argument required"));
+}
+
+#[test]
+fn enable_builtins() {
+    let (status, stdout, stderr) = rush(b"enable -n pwd alias\npwd\nenable -n\nenable -a | grep pwd\nenable pwd nosuch\nenable | grep -c pwd\n");
+    assert_eq!(status, 0);
+    let stdout = stdout.replace("$ ", "");
+    let mut lines = stdout.lines();
+    assert!(lines.next().unwrap().starts_with('/'));
+    assert_eq!(lines.collect::<Vec<_>>(), ["enable -n alias", "enable -n pwd", "enable -n pwd", "1"]);
+    assert!(stderr.contains("enable: nosuch: not a shell builtin"));
+}
+
+#[test]
+fn source_bash_aliases() {
+    let path = format!("{}/bash_aliases", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "# aliases\nalias hi='echo hi'; export RUSH_SOURCED=yes\nf() {\n  echo no\n}\n").unwrap();
+    let input = format!("set -o bashcompat\n. {}\nhi there\necho $RUSH_SOURCED\n", path);
+    let (status, stdout, stderr) = rush(input.as_bytes());
+    assert_eq!(status, 0);
+    assert_eq!(stdout.replace("$ ", ""), "hi there\nyes\n");
+    assert!(stderr.contains("bash_aliases:3: skipping unsupported construct"));
+}
+
+#[test]
+fn command_not_found_hook() {
+    let (_, stdout, stderr) = rush(b"hook on_command_not_found 'echo install $RUSH_COMMAND_NAME for $RUSH_COMMAND_ARGS'\nrush-no-such-command a b\necho $?\n");
+    assert_eq!(stdout.replace("$ ", ""), "install rush-no-such-command for a b\n127\n");
+    assert!(stderr.contains("rush-no-such-command: No such file or directory"));
+}
+
+#[test]
+fn xdg_dirs() {
+    use std::os::unix::fs::PermissionsExt;
+    let root = format!("{}/xdg", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(format!("{}/config/rush", root)).unwrap();
+    std::fs::create_dir_all(format!("{}/legacy", root)).unwrap();
+    std::fs::write(format!("{}/config/rush/logout", root), "echo xdg logout\n").unwrap();
+    std::fs::write(format!("{}/legacy/.rush_history", root), "").unwrap();
+    let input = format!("set -o history\nHOME={0}/home\nXDG_DATA_HOME={0}/data\nXDG_CONFIG_HOME={0}/config\ncd /\nhistory -w\n\
+                         HOME={0}/legacy\nXDG_DATA_HOME=relative\nhistory -w\n", root);
+    let (status, stdout, stderr) = rush_with(&["-l"], input.as_bytes());
+    assert_eq!(status, 0);
+    assert_eq!(stderr, "");
+    assert_eq!(stdout.replace("$ ", ""), "xdg logout\n");
+    let data = std::fs::metadata(format!("{}/data/rush", root)).unwrap();
+    assert_eq!(data.permissions().mode() & 0o777, 0o700);
+    assert!(std::fs::metadata(format!("{}/data/rush/dirs", root)).is_ok());
+    assert!(std::fs::metadata(format!("{}/data/rush/history", root)).is_ok());
+    assert!(!std::fs::read_to_string(format!("{}/legacy/.rush_history", root)).unwrap().is_empty());
+    assert!(std::fs::metadata(format!("{}/legacy/.local", root)).is_err());
+    let piped = format!("{}/piped", root);
+    let (_, stdout, _) = rush_env(&[], &[("XDG_DATA_HOME", piped.as_str())], b"echo hi\nhistory\n");
+    assert_eq!(stdout.replace("$ ", ""), "hi\n");
+    assert!(std::fs::metadata(format!("{}/rush/history", piped)).is_err());
+}
+
+#[test]
+fn config_toml() {
+    let config = format!("{}/config_xdg", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::create_dir_all(format!("{}/rush", config)).unwrap();
+    std::fs::write(format!("{}/rush/config.toml", config), "\
+        [options]\nautopushd = true\nxtrace = 1\n\n\
+        [aliases]\nhi = \"echo hi # there\" # comment\n\n\
+        [history]\nsize = 1_500\ncontrol = ['ignoredups', 'ignorespace']\n\n\
+        [keybindings]\n\"\\\\C-t\" = \"transpose-chars\"\n\n\
+        [colors]\nfg = \"red\"\n").unwrap();
+    let input = b"hi\nset -o | grep autopushd\necho $HISTSIZE $HISTCONTROL\nbind -p | grep C-t\n";
+    let (_, stdout, stderr) = rush_env(&[], &[("XDG_CONFIG_HOME", &config)], input);
+    let stdout = stdout.replace("$ ", "");
+    assert_eq!(stdout, "hi # there\nautopushd       on\n1500 ignoredups:ignorespace\n\"\\C-t\": transpose-chars\n");
+    assert!(stderr.contains("config.toml: line 3: xtrace: expected boolean, found integer"));
+    assert!(stderr.contains("config.toml: line 15: [colors]: unknown table"));
+    assert!(!stderr.contains("line 16"));
+}
+
+#[test]
+fn env_file() {
+    let path = format!("{}/env_file", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "FROM_ENV=yes\nalias greet='echo hello'\n").unwrap();
+    let (status, stdout, _) = rush_env(&[], &[("ENV", "$HOME/env_file")], b"echo $FROM_ENV\ngreet\n");
+    assert_eq!(status, 0);
+    assert_eq!(stdout.replace("$ ", ""), "yes\nhello\n");
+    let (_, _, stderr) = rush_env(&[], &[("ENV", "$HOME/no_env_file")], b"");
+    assert!(stderr.contains("no_env_file: No such file or directory"));
+}
+
+#[test]
+fn rushenv() {
+    use std::os::unix::fs::PermissionsExt;
+    let root = format!("{}/rushenv", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(format!("{}/project/sub", root)).unwrap();
+    std::fs::write(format!("{}/project/.rushenv", root), "# env\nexport PROJECT=rush\nEDITOR=\"$PROJECT vi\"\n1x=y\n").unwrap();
+    let data = format!("{}/data", root);
+    let input = format!("EDITOR=ed\ncd {0}/project\necho $PROJECT $EDITOR\nrushenv allow\necho $PROJECT $EDITOR\n\
+                         cd sub\nprintenv PROJECT\ncd {0}\necho $PROJECT $EDITOR\nrushenv list\n", root);
+    let (status, stdout, stderr) = rush_env(&[], &[("XDG_DATA_HOME", &data)], input.as_bytes());
+    assert_eq!(status, 0);
+    assert_eq!(stdout.replace("$ ", ""), format!("ed\nrush rush vi\nrush\ned\n{}/project\n", root));
+    assert!(stderr.contains(&format!("rush: {}/project/.rushenv is not allowed", root)));
+    assert!(stderr.contains(".rushenv: line 4: invalid assignment"));
+    let allow = std::fs::metadata(format!("{}/rush/rushenv_allow", data)).unwrap();
+    assert_eq!(allow.permissions().mode() & 0o777, 0o600);
+    let input = format!("cd {}/project\necho $PROJECT\nrushenv deny\necho $PROJECT\n", root);
+    let (_, stdout, _) = rush_env(&[], &[("XDG_DATA_HOME", &data)], input.as_bytes());
+    assert_eq!(stdout.replace("$ ", ""), "rush\n\n");
+}
+
+#[test]
+fn restricted() {
+    let input = b"cd /\nPATH=/tmp\nexport SHELL=/bin/sh\n/bin/echo no\necho no > out\n. /dev/null\nhistory -w /tmp/h\n\
+                  FOO=bar\necho $FOO | cat\npwd\n";
+    let (status, stdout, stderr) = rush_with(&["-r"], input);
+    assert_eq!(status, 0);
+    assert_eq!(stdout.replace("$ ", ""), format!("bar\n{}\n", env!("CARGO_MANIFEST_DIR")));
+    for message in ["cd: restricted", "PATH: readonly variable", "SHELL: readonly variable",
+                    "/bin/echo: cannot specify `/' in command names", "out: cannot redirect output",
+                    ".: /dev/null: cannot specify `/' in file names", "history: /tmp/h: cannot specify `/' in file names"] {
+        assert!(stderr.contains(&format!("Restricted: {}\n", message)), "{}", message);
+    }
+    let input = b"compgen -C /tmp/escape.sh x\ncomplete -C/tmp/escape.sh ls\ncomplete --load /tmp/escape.bash\n\
+                  bind -f /tmp/inputrc\nhistory --import bash /tmp/bash_history\nHISTFILE=out\nexport VISUAL=sh\n\
+                  EDITOR=sh\nFCEDIT=sh\nTMPDIR=.\nfc\nfc -e sh 1\nfc -l\n";
+    let (_, _, stderr) = rush_with(&["-r"], input);
+    for message in ["compgen: /tmp/escape.sh: cannot specify `/' in command names",
+                    "complete: /tmp/escape.sh: cannot specify `/' in command names", "complete: --load: restricted",
+                    "bind: /tmp/inputrc: cannot specify `/' in file names",
+                    "history: /tmp/bash_history: cannot specify `/' in file names", "HISTFILE: readonly variable",
+                    "VISUAL: readonly variable", "EDITOR: readonly variable", "FCEDIT: readonly variable",
+                    "TMPDIR: readonly variable", "fc: cannot run an editor"] {
+        assert!(stderr.contains(&format!("Restricted: {}\n", message)), "{}", message);
+    }
+    let (_, stdout, _) = rush(b"/bin/echo yes\n");
+    assert_eq!(stdout.replace("$ ", ""), "yes\n");
+}
+
+#[test]
+fn profiles() {
+    let root = format!("{}/profiles", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(format!("{}/config/rush/profiles", root)).unwrap();
+    std::fs::write(format!("{}/config/rush/config.toml", root), "[aliases]\nwho = \"echo personal\"\n").unwrap();
+    std::fs::write(format!("{}/config/rush/profiles/work.toml", root),
+                   "[aliases]\nwho = \"echo work\"\n\n[env]\nWORK_DIR = \"$HOME/work\"\n\n[prompt]\ntheme = \"git\"\n").unwrap();
+    let vars = [("XDG_CONFIG_HOME", format!("{}/config", root)), ("XDG_DATA_HOME", format!("{}/data", root)),
+                ("PS1", "$ ".to_owned())];
+    let vars: Vec<_> = vars.iter().map(|(name, value)| (*name, value.as_str())).collect();
+    let input = b"who\nprintenv WORK_DIR RUSH_PROFILE\necho $RUSH_THEME\nhistory -w\n";
+    let (status, stdout, stderr) = rush_env(&["--profile", "work"], &vars, input);
+    assert_eq!(status, 0);
+    assert_eq!(stderr, "");
+    assert_eq!(stdout.replace("$ ", ""), format!("work\n{}/work\nwork\ngit\n", env!("CARGO_TARGET_TMPDIR")));
+    assert!(std::fs::metadata(format!("{}/data/rush/history-work", root)).is_ok());
+    let (_, stdout, _) = rush_env(&[], &vars, b"who\n");
+    assert_eq!(stdout.replace("$ ", ""), "personal\n");
+    let (_, _, stderr) = rush_env(&["--profile=home"], &vars, b"");
+    assert!(stderr.contains("profiles/home.toml: No such file or directory"));
+}
+
+#[test]
+fn color_capabilities() {
+    let input = b"RUSH_THEME=minimal\necho |\n";
+    let (_, stdout, stderr) = rush_env(&[], &[("TERM", "xterm")], input);
+    assert!(!stdout.contains('\x1b') && !stderr.contains('\x1b'));
+    let (_, stdout, stderr) = rush_env(&[], &[("CLICOLOR_FORCE", "1")], input);
+    assert!(stdout.contains("\x1b[1;34m"));
+    assert!(stderr.starts_with("\x1b[31mParsing Error: "));
+    let (_, stdout, stderr) = rush_env(&[], &[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")], input);
+    assert!(!stdout.contains('\x1b') && !stderr.contains('\x1b'));
+}
+
+#[test]
+fn concurrent_dir_visits() {
+    let root = format!("{}/frecency", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let vars = [("HOME", root.as_str()), ("XDG_DATA_HOME", root.as_str())];
+    let input = "cd /\n".repeat(25);
+    std::thread::scope(|scope| {
+        for _ in 0 .. 4 {
+            scope.spawn(|| rush_env(&[], &vars, input.as_bytes()));
+        }
+    });
+    let dirs = std::fs::read_to_string(format!("{}/rush/dirs", root)).unwrap();
+    assert!(dirs.starts_with("/|100|"), "{}", dirs);
+    std::fs::remove_file(format!("{}/rush/dirs", root)).unwrap();
+    std::fs::create_dir(format!("{}/rush/dirs", root)).unwrap();
+    let (_, _, stderr) = rush_env(&[], &vars, b"cd /\ncd /\n");
+    assert_eq!(stderr.matches("rush/dirs: Is a directory").count(), 1, "{}", stderr);
+}
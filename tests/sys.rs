@@ -0,0 +1,45 @@
+extern crate libc;
+extern crate rush;
+
+use rush::sys::{chdir, errno, execvp, openr, openw, retry, set_errno, strftime};
+
+#[test]
+fn interior_nul() {
+    assert_eq!(chdir("/\0tmp"), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(openr("in\0"), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(openw("out\0"), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(execvp(&["true\0".to_owned()]), -1);
+    assert_eq!(errno(), libc::EINVAL);
+    assert_eq!(strftime("%H\0", 0), "");
+}
+
+#[test]
+fn empty_command() {
+    assert_eq!(execvp(&[]), -1);
+    assert_eq!(errno(), libc::ENOENT);
+}
+
+#[test]
+fn retry_on_eintr() {
+    let mut calls = 0;
+    let ret = retry(|| {
+        calls += 1;
+        if calls < 3 {
+            set_errno(libc::EINTR);
+            -1
+        } else {
+            7
+        }
+    });
+    assert_eq!((ret, calls), (7, 3));
+    let mut calls = 0;
+    let ret = retry(|| {
+        calls += 1;
+        set_errno(libc::EBADF);
+        -1
+    });
+    assert_eq!((ret, calls), (-1, 1));
+}